@@ -1,3 +1,3 @@
 -- Minified by luamine-rs v0.1.0
 -- Code may not be usable, report bugs at: https://github.com/teppyboy/luamine-rs/issues
-var2,var10,var6,var7,var8="amogus2",4,"555","amogus3","segs";local var1,var4,var5,var666,var777,var123,var456,var3,var9="amogus",1,"amogus",nil,nil,1,nil,3,"argv";function python(sksk,pypy)xoxo,varpython,superamogus='susu','python123',true;local x,y,z,e,t,a=999,666,'discord','discord','discord','discord';print(var1,var2,var8,var9,var7);print(sksk);end;local function javascript(js,deno)print(js);print("deno good");end;python("anti rust","python no 1");javascript("javascript","deno");
\ No newline at end of file
+local a:string,b:number,b:string="amogus",1,"amogus"var2,var10,var6="amogus2",4,"555"local b,b local b:number,b:string=1 local b:number=3 local b="argv"var7="amogus3"var8="segs"function python(c,d)local d=999 local d=666 local d,d,d,d='discord','discord','discord','discord'xoxo='susu'varpython='python123'superamogus=true print(a,var2,var8,b,var7)print(c)end local function a(a,b)print(a)print("deno good")end python("anti rust","python no 1")a("javascript","deno")
\ No newline at end of file
@@ -0,0 +1,771 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    ops::Range,
+    path::{Path, PathBuf, MAIN_SEPARATOR_STR},
+};
+
+use full_moon::{
+    ast::{
+        luau::{ExportedTypeDeclaration, ExportedTypeFunction, TypeInfo},
+        Assignment, Ast, Call, CompoundAssignment, Expression, FunctionArgs, FunctionBody,
+        FunctionCall, FunctionDeclaration, Index, LocalAssignment, Prefix, Suffix, Var,
+    },
+    node::Node,
+    tokenizer::{Token, TokenReference, TokenType},
+    visitors::{Visit, Visitor},
+};
+use log::{debug, warn};
+
+use crate::{
+    error::ParseError,
+    minifier::{
+        header,
+        literals::{decode_string, string_literal},
+        scope::ScopeTree,
+    },
+    rojo::{self, Project},
+    Error, Position, Target,
+};
+
+/// The function which replaces `require` for bundled modules
+const REQUIRE: &str = "__bundle_require";
+
+/// Loads bundled modules once, like `require` does. The loader table doubles as the marker of a
+/// module which is loading, so requiring it again fails like it does in Lua 5.1.
+const RUNTIME: &str = "local __bundle_loaders, __bundle_loaded = {}, {}
+local function __bundle_require(name)
+\tlocal value = __bundle_loaded[name]
+\tif value == nil then
+\t\t__bundle_loaded[name] = __bundle_loaders
+\t\tvalue = __bundle_loaders[name](name)
+\t\tif value == nil then
+\t\t\tvalue = true
+\t\tend
+\t\t__bundle_loaded[name] = value
+\telseif value == __bundle_loaders then
+\t\terror(\"loop or previous error loading module '\" .. name .. \"'\", 2)
+\tend
+\treturn value
+end
+";
+
+/// How `bundle` finds and reads modules
+#[derive(Clone, Debug)]
+pub struct BundleOptions {
+    /// Templates separated by `;` like `package.path`, where `?` stands for the module name
+    /// with its dots replaced by directory separators. Relative templates are relative to the
+    /// current directory, as they are for `require`.
+    pub package_path: String,
+    /// Modules left to the `require` of the runtime, like C modules
+    pub externals: HashSet<String>,
+    /// The Lua version the modules are written in
+    pub dialect: Target,
+    /// A Rojo `*.project.json` file, which lets requires of instances like
+    /// `require(script.Parent.Util)` be bundled
+    pub rojo_project: Option<PathBuf>,
+}
+
+impl Default for BundleOptions {
+    fn default() -> Self {
+        BundleOptions {
+            package_path: String::from("./?.lua;./?/init.lua;./?.luau;./?/init.luau"),
+            externals: HashSet::new(),
+            dialect: Target::Luau,
+            rojo_project: None,
+        }
+    }
+}
+
+/// A single chunk which runs the entry point with every module it requires
+#[derive(Clone, Debug)]
+pub struct Bundle {
+    pub code: String,
+    /// The bundled modules, in the order they were found
+    pub modules: Vec<BundledModule>,
+}
+
+#[derive(Clone, Debug)]
+pub struct BundledModule {
+    /// The name the module is required with
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// A `require` call with a string literal, or with an instance of the Rojo project
+struct Require {
+    /// The module name, or the instance path written like `game.ReplicatedStorage.Util`
+    module: String,
+    /// The instance path of a required instance
+    instance: Option<Vec<String>>,
+    /// What `replacement` replaces in the source when the module is bundled
+    range: Range<usize>,
+    replacement: String,
+    position: Position,
+    /// Whether the call runs while the file loads, rather than in a function it defines
+    eager: bool,
+}
+
+/// A `require` call whose argument may be an instance, resolved once the whole file is read
+struct InstanceRequire {
+    argument: Expression,
+    /// The `require` call up to the end of its arguments
+    range: Range<usize>,
+    position: Position,
+    eager: bool,
+}
+
+/// Finds the calls of the global `require` outside of type annotations
+struct Requires<'a> {
+    /// Byte offsets of the references to the global `require`
+    globals: &'a HashSet<usize>,
+    dialect: Target,
+    /// Whether requires of instances are looked for, only with a Rojo project
+    roblox: bool,
+    functions: usize,
+    types: usize,
+    found: Vec<Require>,
+    instances: Vec<InstanceRequire>,
+    /// Where the calls with any other argument are
+    dynamic: Vec<Position>,
+    /// Where the `export` of each exported type is
+    exports: Vec<Range<usize>>,
+    /// The value of each local, by the byte offset of its declaration
+    values: HashMap<usize, Expression>,
+    /// Byte offsets of the names which are assigned to
+    writes: HashSet<usize>,
+}
+
+fn token_range(token: &TokenReference) -> Range<usize> {
+    token.token().start_position().bytes()..token.token().end_position().bytes()
+}
+
+impl Requires<'_> {
+    /// The module a call requires, if its only argument is a string literal
+    fn module(&self, args: &FunctionArgs) -> Option<String> {
+        let token = match args {
+            FunctionArgs::String(token) => token,
+            FunctionArgs::Parentheses { arguments, .. } => match arguments.iter().next() {
+                Some(Expression::String(token)) if arguments.len() == 1 => token,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        String::from_utf8(decode_string(token.token(), self.dialect)?).ok()
+    }
+}
+
+impl Visitor for Requires<'_> {
+    fn visit_function_body(&mut self, _: &FunctionBody) {
+        self.functions += 1;
+    }
+
+    fn visit_function_body_end(&mut self, _: &FunctionBody) {
+        self.functions -= 1;
+    }
+
+    fn visit_type_info(&mut self, _: &TypeInfo) {
+        self.types += 1;
+    }
+
+    fn visit_type_info_end(&mut self, _: &TypeInfo) {
+        self.types -= 1;
+    }
+
+    fn visit_exported_type_declaration(&mut self, declaration: &ExportedTypeDeclaration) {
+        self.exports.push(token_range(declaration.export_token()));
+    }
+
+    fn visit_exported_type_function(&mut self, function: &ExportedTypeFunction) {
+        self.exports.push(token_range(function.export_token()));
+    }
+
+    fn visit_function_call(&mut self, call: &FunctionCall) {
+        let Prefix::Name(name) = call.prefix() else {
+            return;
+        };
+        let start = name.token().start_position();
+        if self.types > 0 || !self.globals.contains(&start.bytes()) {
+            return;
+        }
+        let Some(Suffix::Call(Call::AnonymousCall(args))) = call.suffixes().next() else {
+            return;
+        };
+        let eager = self.functions == 0;
+        if let Some(module) = self.module(args) {
+            self.found.push(Require {
+                module,
+                instance: None,
+                range: token_range(name),
+                replacement: String::from(REQUIRE),
+                position: start.into(),
+                eager,
+            });
+            return;
+        }
+        match args {
+            FunctionArgs::Parentheses { arguments, .. } if self.roblox && arguments.len() == 1 => {
+                let end = args.end_position().map_or(start.bytes(), |end| end.bytes());
+                self.instances.push(InstanceRequire {
+                    argument: arguments.iter().next().unwrap().clone(),
+                    range: start.bytes()..end,
+                    position: start.into(),
+                    eager,
+                });
+            }
+            _ => self.dynamic.push(start.into()),
+        }
+    }
+
+    fn visit_local_assignment(&mut self, assignment: &LocalAssignment) {
+        if self.roblox {
+            for (name, value) in assignment.names().iter().zip(assignment.expressions()) {
+                let position = name.token().start_position().bytes();
+                self.values.insert(position, value.clone());
+            }
+        }
+    }
+
+    fn visit_assignment(&mut self, assignment: &Assignment) {
+        for var in assignment.variables() {
+            if let Var::Name(name) = var {
+                self.writes.insert(name.token().start_position().bytes());
+            }
+        }
+    }
+
+    fn visit_compound_assignment(&mut self, assignment: &CompoundAssignment) {
+        if let Var::Name(name) = assignment.lhs() {
+            self.writes.insert(name.token().start_position().bytes());
+        }
+    }
+
+    fn visit_function_declaration(&mut self, declaration: &FunctionDeclaration) {
+        let name = declaration.name();
+        if name.names().len() == 1 && name.method_name().is_none() {
+            let name = name.names().iter().next().unwrap();
+            self.writes.insert(name.token().start_position().bytes());
+        }
+    }
+}
+
+/// Resolves expressions to the instances of a Rojo project, the way they evaluate in Roblox
+struct Instances<'a> {
+    /// The instance path of the file, if the project has it
+    script: Option<&'a [String]>,
+    dialect: Target,
+    /// The binding each local name refers to
+    bindings: HashMap<usize, usize>,
+    /// The value of each local which is never assigned to after its declaration
+    values: HashMap<usize, &'a Expression>,
+}
+
+impl<'a> Instances<'a> {
+    fn new(
+        scopes: &ScopeTree,
+        script: Option<&'a [String]>,
+        dialect: Target,
+        values: &'a HashMap<usize, Expression>,
+        writes: &HashSet<usize>,
+    ) -> Self {
+        let bindings = scopes.binding_positions();
+        let values = values
+            .iter()
+            .filter_map(|(position, value)| {
+                let binding = &scopes.bindings[*bindings.get(position)?];
+                let written = binding
+                    .positions
+                    .iter()
+                    .any(|position| writes.contains(position));
+                (!written).then_some((bindings[position], value))
+            })
+            .collect();
+        Instances {
+            script,
+            dialect,
+            bindings,
+            values,
+        }
+    }
+
+    /// The string an argument is, if it's only a string literal
+    fn string(&self, expression: &Expression) -> Option<String> {
+        match expression {
+            Expression::String(token) => {
+                String::from_utf8(decode_string(token.token(), self.dialect)?).ok()
+            }
+            Expression::Parentheses { expression, .. } => self.string(expression),
+            _ => None,
+        }
+    }
+
+    /// The instance path of an expression, `None` if it can't be known without running the
+    /// code, or why it's not an instance
+    fn resolve(&self, expression: &Expression) -> Result<Option<Vec<String>>, String> {
+        match expression {
+            Expression::Parentheses { expression, .. } => self.resolve(expression),
+            Expression::TypeAssertion { expression, .. } => self.resolve(expression),
+            Expression::Var(Var::Name(name)) => self.name(name),
+            Expression::Var(Var::Expression(var)) => self.suffixes(var.prefix(), var.suffixes()),
+            Expression::FunctionCall(call) => self.suffixes(call.prefix(), call.suffixes()),
+            _ => Ok(None),
+        }
+    }
+
+    fn name(&self, name: &TokenReference) -> Result<Option<Vec<String>>, String> {
+        let position = name.token().start_position().bytes();
+        if let Some(binding) = self.bindings.get(&position) {
+            return match self.values.get(binding) {
+                Some(value) => self.resolve(value),
+                None => Ok(None),
+            };
+        }
+        match name.token().to_string().as_str() {
+            "script" => match self.script {
+                Some(script) => Ok(Some(script.to_vec())),
+                None => Err(String::from(
+                    "`script` is unknown, this file isn't in the Rojo project",
+                )),
+            },
+            "game" => Ok(Some(Vec::new())),
+            "workspace" => Ok(Some(vec![String::from("Workspace")])),
+            _ => Ok(None),
+        }
+    }
+
+    fn suffixes<'b>(
+        &self,
+        prefix: &Prefix,
+        suffixes: impl Iterator<Item = &'b Suffix>,
+    ) -> Result<Option<Vec<String>>, String> {
+        let path = match prefix {
+            Prefix::Name(name) => self.name(name)?,
+            Prefix::Expression(expression) => self.resolve(expression)?,
+            _ => None,
+        };
+        let Some(mut path) = path else {
+            return Ok(None);
+        };
+        for suffix in suffixes {
+            let child = match suffix {
+                Suffix::Index(Index::Dot { name, .. }) => name.token().to_string(),
+                Suffix::Index(Index::Brackets { expression, .. }) => {
+                    match self.string(expression) {
+                        Some(name) => name,
+                        None => return Ok(None),
+                    }
+                }
+                Suffix::Call(Call::MethodCall(call)) => {
+                    let argument = match call.args() {
+                        FunctionArgs::String(token) => decode_string(token.token(), self.dialect)
+                            .and_then(|bytes| String::from_utf8(bytes).ok()),
+                        FunctionArgs::Parentheses { arguments, .. } => {
+                            arguments.iter().next().and_then(|first| self.string(first))
+                        }
+                        _ => None,
+                    };
+                    let Some(argument) = argument else {
+                        return Ok(None);
+                    };
+                    match call.name().token().to_string().as_str() {
+                        "GetService" if path.is_empty() => path.push(argument),
+                        "WaitForChild" | "FindFirstChild" => path.push(argument),
+                        "FindFirstAncestor" => {
+                            path.pop();
+                            while path.last().is_some_and(|name| *name != argument) {
+                                path.pop();
+                            }
+                            if path.is_empty() {
+                                return Err(format!("no ancestor is named `{}`", argument));
+                            }
+                        }
+                        _ => return Ok(None),
+                    }
+                    continue;
+                }
+                _ => return Ok(None),
+            };
+            if child == "Parent" {
+                if path.pop().is_none() {
+                    return Err(String::from("game has no parent"));
+                }
+            } else {
+                path.push(child);
+            }
+        }
+        Ok(Some(path))
+    }
+}
+
+/// A file of the bundle
+struct File {
+    path: PathBuf,
+    text: String,
+    ast: Ast,
+    requires: Vec<Require>,
+    exports: Vec<Range<usize>>,
+    shebang: Option<Range<usize>>,
+}
+
+impl File {
+    fn read(path: &Path, dialect: Target, project: Option<&Project>) -> Result<Self, Error> {
+        let text = fs::read_to_string(path).map_err(|error| Error::io(path, error))?;
+        let in_file = |text: &str, error| Error::Module {
+            path: path.to_path_buf(),
+            text: text.to_string(),
+            error: Box::new(error),
+        };
+        let ast = match full_moon::parse_fallible(&text, dialect.lua_version()).into_result() {
+            Ok(ast) => ast,
+            Err(errors) => {
+                let error = Error::Parse(errors.into_iter().map(ParseError::from).collect());
+                return Err(in_file(&text, error));
+            }
+        };
+        let scopes = ScopeTree::build(ast.nodes()).map_err(|error| in_file(&text, error))?;
+        let globals = scopes
+            .globals
+            .iter()
+            .filter(|global| global.name == "require")
+            .flat_map(|global| global.positions.iter().copied())
+            .collect();
+        let mut requires = Requires {
+            globals: &globals,
+            dialect,
+            roblox: project.is_some(),
+            functions: 0,
+            types: 0,
+            found: Vec::new(),
+            instances: Vec::new(),
+            dynamic: Vec::new(),
+            exports: Vec::new(),
+            values: HashMap::new(),
+            writes: HashSet::new(),
+        };
+        ast.nodes().visit(&mut requires);
+        if let Some(project) = project {
+            let instances = Instances::new(
+                &scopes,
+                project.instance_path(path),
+                dialect,
+                &requires.values,
+                &requires.writes,
+            );
+            for require in &requires.instances {
+                match instances.resolve(&require.argument) {
+                    Ok(Some(instance)) => {
+                        let module = rojo::display(&instance);
+                        let name = Token::new(string_literal(module.as_bytes(), dialect, false));
+                        requires.found.push(Require {
+                            replacement: format!("{}({})", REQUIRE, name),
+                            module,
+                            instance: Some(instance),
+                            range: require.range.clone(),
+                            position: require.position,
+                            eager: require.eager,
+                        });
+                    }
+                    Ok(None) => requires.dynamic.push(require.position),
+                    Err(message) => {
+                        let error = Error::Require {
+                            message,
+                            position: require.position,
+                        };
+                        return Err(in_file(&text, error));
+                    }
+                }
+            }
+            requires.found.sort_by_key(|require| require.range.start);
+        }
+        let bundled = match project {
+            Some(_) => "a string literal or of an instance of the Rojo project",
+            None => "a string literal",
+        };
+        requires.dynamic.sort_by_key(|position| position.offset);
+        for position in requires.dynamic {
+            warn!(
+                "{}:{}:{}: only requires of {} are bundled, this one is left to the runtime",
+                path.display(),
+                position.line,
+                position.column,
+                bundled
+            );
+        }
+        let (found, exports) = (requires.found, requires.exports);
+        let shebang = header::kept_comments(&ast)
+            .find(|token| matches!(token.token_type(), TokenType::Shebang { .. }))
+            .map(|token| token.start_position().bytes()..token.end_position().bytes());
+        Ok(File {
+            path: path.to_path_buf(),
+            text,
+            ast,
+            requires: found,
+            exports,
+            shebang,
+        })
+    }
+
+    fn error(&self, error: Error) -> Error {
+        Error::Module {
+            path: self.path.clone(),
+            text: self.text.clone(),
+            error: Box::new(error),
+        }
+    }
+
+    /// The source with the requires of bundled modules rewritten and the shebang removed, as
+    /// it can only be the first line of the bundle. Modules lose their type exports too, their
+    /// types become local to their loader.
+    fn rewrite(&self, bundled: &HashMap<String, usize>, module: bool) -> String {
+        let mut edits: Vec<(Range<usize>, &str)> = self
+            .requires
+            .iter()
+            .filter(|require| bundled.contains_key(&require.module))
+            .map(|require| (require.range.clone(), require.replacement.as_str()))
+            .collect();
+        edits.extend(self.shebang.iter().map(|range| (range.clone(), "")));
+        if module {
+            edits.extend(self.exports.iter().map(|range| (range.clone(), "")));
+        }
+        edits.sort_by_key(|(range, _)| range.start);
+        let mut text = String::new();
+        let mut last = 0;
+        for (range, replacement) in edits {
+            text.push_str(&self.text[last..range.start]);
+            text.push_str(replacement);
+            last = range.end;
+        }
+        text.push_str(&self.text[last..]);
+        text
+    }
+}
+
+/// Finds the file of a module with the templates of `package_path`, or returns the paths
+/// which were tried
+fn resolve(module: &str, package_path: &str) -> Result<PathBuf, Vec<String>> {
+    let name = module.replace('.', MAIN_SEPARATOR_STR);
+    let mut tried = Vec::new();
+    for template in package_path
+        .split(';')
+        .filter(|template| !template.is_empty())
+    {
+        let path = template.replace('?', &name);
+        if Path::new(&path).is_file() {
+            return Ok(PathBuf::from(path));
+        }
+        tried.push(path);
+    }
+    Err(tried)
+}
+
+/// Colors of the depth first search for cycles
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    New,
+    Loading,
+    Loaded,
+}
+
+/// Fails if a file requires, while it loads, a module which is still loading
+fn check_cycles(
+    files: &[File],
+    names: &[String],
+    ids: &HashMap<String, usize>,
+    file: usize,
+    visits: &mut [State],
+    stack: &mut Vec<usize>,
+) -> Result<(), Error> {
+    visits[file] = State::Loading;
+    stack.push(file);
+    for require in files[file].requires.iter().filter(|require| require.eager) {
+        let Some(&id) = ids.get(&require.module) else {
+            continue;
+        };
+        match visits[id] {
+            State::New => check_cycles(files, names, ids, id, visits, stack)?,
+            State::Loading => {
+                let start = stack.iter().position(|&loading| loading == id).unwrap();
+                let cycle: Vec<&str> = stack[start..]
+                    .iter()
+                    .chain([&id])
+                    .map(|&id| names[id].as_str())
+                    .collect();
+                return Err(files[file].error(Error::Require {
+                    message: format!(
+                        "modules require each other while loading: {}",
+                        cycle.join(" -> ")
+                    ),
+                    position: require.position,
+                }));
+            }
+            State::Loaded => {}
+        }
+    }
+    stack.pop();
+    visits[file] = State::Loaded;
+    Ok(())
+}
+
+/// Bundles the script at `entry` with every module it requires with a string literal, or with
+/// an instance of the Rojo project, and the modules those require in turn, into a single chunk.
+/// Modules are loaded when they are first required, once, and `require` calls of anything else
+/// are left to the runtime.
+pub fn bundle(entry: &Path, options: &BundleOptions) -> Result<Bundle, Error> {
+    let project = match &options.rojo_project {
+        Some(path) => Some(Project::load(path)?),
+        None => None,
+    };
+    let project = project.as_ref();
+    let mut files = vec![File::read(entry, options.dialect, project)?];
+    // The name of each file, the entry is named by its path
+    let mut names = vec![entry.display().to_string()];
+    let mut ids: HashMap<String, usize> = HashMap::new();
+    let mut next = 0;
+    while next < files.len() {
+        let mut found = Vec::new();
+        for require in &files[next].requires {
+            if ids.contains_key(&require.module) || options.externals.contains(&require.module) {
+                continue;
+            }
+            let path = match (&require.instance, project) {
+                (Some(instance), Some(project)) => project.module(instance).map(Path::to_path_buf),
+                _ => resolve(&require.module, &options.package_path).map_err(|tried| {
+                    format!(
+                        "can't find module `{}`, tried {}",
+                        require.module,
+                        tried.join(", ")
+                    )
+                }),
+            }
+            .map_err(|message| {
+                files[next].error(Error::Require {
+                    message,
+                    position: require.position,
+                })
+            })?;
+            debug!("Resolved module {} to {}", require.module, path.display());
+            ids.insert(require.module.clone(), files.len() + found.len());
+            found.push((require.module.clone(), path));
+        }
+        for (name, path) in found {
+            files.push(File::read(&path, options.dialect, project)?);
+            names.push(name);
+        }
+        next += 1;
+    }
+    // Modules which are only required lazily still load their own eager requires
+    let mut visits = vec![State::New; files.len()];
+    for file in 0..files.len() {
+        if visits[file] == State::New {
+            check_cycles(&files, &names, &ids, file, &mut visits, &mut Vec::new())?;
+        }
+    }
+
+    // The shebang and directives of the entry, then the license comments of every file
+    let mut kept: Vec<&Token> = header::kept_comments(&files[0].ast).collect();
+    for file in &files[1..] {
+        kept.extend(
+            header::kept_comments(&file.ast)
+                .filter(|token| matches!(token.token_type(), TokenType::MultiLineComment { .. })),
+        );
+    }
+    let mut code = header::lines(kept);
+    code.push_str(RUNTIME);
+    for (file, name) in files.iter().zip(&names).skip(1) {
+        // A comment on the last line would swallow the `end`
+        let name = Token::new(string_literal(name.as_bytes(), Target::Lua51, false));
+        code.push_str(&format!(
+            "__bundle_loaders[{}] = function(...)\n{}\nend\n",
+            name,
+            file.rewrite(&ids, true)
+        ));
+    }
+    code.push_str(&files[0].rewrite(&ids, false));
+    let modules = files
+        .iter()
+        .zip(names)
+        .skip(1)
+        .map(|(file, name)| BundledModule {
+            name,
+            path: file.path.clone(),
+        })
+        .collect();
+    Ok(Bundle { code, modules })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `files` to a fresh directory and bundles its `main.lua`
+    fn bundled(test: &str, files: &[(&str, &str)]) -> Result<Bundle, Error> {
+        let dir =
+            std::env::temp_dir().join(format!("luamine-bundle-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (name, code) in files {
+            fs::write(dir.join(name), code).unwrap();
+        }
+        let options = BundleOptions {
+            package_path: format!("{}/?.lua", dir.display()),
+            dialect: Target::Lua54,
+            ..BundleOptions::default()
+        };
+        let result = bundle(&dir.join("main.lua"), &options);
+        fs::remove_dir_all(&dir).unwrap();
+        result
+    }
+
+    #[test]
+    fn finds_cycles_behind_lazy_requires() {
+        let error = bundled(
+            "cycle",
+            &[
+                (
+                    "main.lua",
+                    "local function f() return require('a') end return f",
+                ),
+                ("a.lua", "return require('b')"),
+                ("b.lua", "return require('a')"),
+            ],
+        )
+        .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("modules require each other while loading: a -> b -> a"),
+            "{error}"
+        );
+        // Modules which only require each other from functions load fine
+        let bundle = bundled(
+            "lazy",
+            &[
+                ("main.lua", "return require('a')"),
+                ("a.lua", "return function() return require('b') end"),
+                ("b.lua", "return require('a')"),
+            ],
+        )
+        .unwrap();
+        let names: Vec<&str> = bundle
+            .modules
+            .iter()
+            .map(|module| module.name.as_str())
+            .collect();
+        assert_eq!(names, ["a", "b"]);
+    }
+
+    #[test]
+    fn reports_unresolved_modules() {
+        let error =
+            bundled("missing", &[("main.lua", "local util = require('util')")]).unwrap_err();
+        let Error::Module { path, error, .. } = &error else {
+            panic!("{error:?}");
+        };
+        assert!(path.ends_with("main.lua"));
+        assert!(
+            matches!(error.as_ref(), Error::Require { message, position }
+                if message.starts_with("can't find module `util`") && position.column == 14),
+            "{error:?}"
+        );
+    }
+}
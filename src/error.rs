@@ -0,0 +1,200 @@
+use std::{fmt, io, path::PathBuf};
+
+/// A location in the source
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+    /// Byte offset from the start of the source
+    pub offset: usize,
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column, counted in characters
+    pub column: usize,
+}
+
+impl Position {
+    /// Finds the line and column of a byte offset in `source`
+    pub fn from_offset(source: &str, offset: usize) -> Self {
+        let before = &source[..offset.min(source.len())];
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+        Position {
+            offset,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+impl From<full_moon::tokenizer::Position> for Position {
+    fn from(position: full_moon::tokenizer::Position) -> Self {
+        Position {
+            offset: position.bytes(),
+            line: position.line(),
+            column: position.character(),
+        }
+    }
+}
+
+/// A syntax error reported by full-moon
+#[derive(Clone, Debug)]
+pub struct ParseError {
+    pub message: String,
+    pub start: Position,
+    pub end: Position,
+}
+
+impl From<full_moon::Error> for ParseError {
+    fn from(error: full_moon::Error) -> Self {
+        let (start, end) = error.range();
+        let message = match &error {
+            // The tokenizer error's own message ends with its range, which we show separately
+            full_moon::Error::TokenizerError(error) => error.error().to_string(),
+            _ => error.error_message().into_owned(),
+        };
+        ParseError {
+            message,
+            start: start.into(),
+            end: end.into(),
+        }
+    }
+}
+
+/// Why a chunk couldn't be minified
+#[derive(Debug)]
+pub enum Error {
+    /// The source isn't valid Lua(u)
+    Parse(Vec<ParseError>),
+    /// A file couldn't be read or written
+    Io { path: PathBuf, source: io::Error },
+    /// The source uses a construct which the minifier can't handle safely
+    Unsupported { message: String, position: Position },
+    /// The minified code doesn't have the same structure as the source
+    Verify {
+        /// Where the first differing statement starts in the source
+        position: Position,
+        expected: String,
+        found: String,
+    },
+    /// A source map or rename map is malformed
+    InvalidMap(String),
+    /// A Rojo project file is malformed
+    InvalidProject { path: PathBuf, message: String },
+    /// A module of a bundle can't be found, or modules require each other while loading
+    Require { message: String, position: Position },
+    /// An error in another file than the one being processed, like a module of a bundle
+    Module {
+        path: PathBuf,
+        /// The source of the file
+        text: String,
+        error: Box<Error>,
+    },
+}
+
+impl Error {
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Error::Io {
+            path: path.into(),
+            source,
+        }
+    }
+
+    /// Renders the error like a compiler diagnostic, quoting the lines of `source` it points at
+    pub fn diagnostic(&self, path: &str, source: &str) -> String {
+        match self {
+            Error::Parse(errors) => errors
+                .iter()
+                .map(|error| snippet(&error.message, path, source, error.start, error.end))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            Error::Io { .. } => format!("error: {}\n", self),
+            Error::InvalidMap(_) => format!("error: {}: {}\n", path, self),
+            Error::InvalidProject { .. } => format!("error: {}\n", self),
+            Error::Unsupported { message, position } | Error::Require { message, position } => {
+                snippet(message, path, source, *position, *position)
+            }
+            Error::Module { path, text, error } => error.diagnostic(&path.to_string_lossy(), text),
+            Error::Verify {
+                position,
+                expected,
+                found,
+            } => {
+                let message = "minified code differs from the source";
+                let gutter = " ".repeat(position.line.to_string().len());
+                format!(
+                    "{}{gutter} = expected: {expected}\n{gutter} = found:    {found}\n",
+                    snippet(message, path, source, *position, *position)
+                )
+            }
+        }
+    }
+}
+
+/// Formats `message` followed by the line of `source` where `start` is, with the range up to
+/// `end` underlined
+fn snippet(message: &str, path: &str, source: &str, start: Position, end: Position) -> String {
+    let line = source.lines().nth(start.line - 1).unwrap_or_default();
+    let gutter = " ".repeat(start.line.to_string().len());
+    let width = if end.line == start.line && end.column > start.column {
+        end.column - start.column
+    } else {
+        1
+    };
+    // Keep tabs so the marker lines up with the quoted line
+    let indent: String = line
+        .chars()
+        .take(start.column.saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    format!(
+        "error: {message}\n{gutter}--> {path}:{}:{}\n{gutter} |\n{} | {line}\n{gutter} | {indent}{}\n",
+        start.line,
+        start.column,
+        start.line,
+        "^".repeat(width),
+    )
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(errors) => {
+                write!(f, "failed to parse the source")?;
+                for error in errors {
+                    write!(
+                        f,
+                        "\n{}:{}: {}",
+                        error.start.line, error.start.column, error.message
+                    )?;
+                }
+                Ok(())
+            }
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Unsupported { message, position } | Error::Require { message, position } => {
+                write!(f, "{}:{}: {}", position.line, position.column, message)
+            }
+            Error::Module { path, error, .. } => write!(f, "{}: {}", path.display(), error),
+            Error::Verify {
+                position,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{}:{}: minified code differs from the source, expected `{}`, found `{}`",
+                position.line, position.column, expected, found
+            ),
+            Error::InvalidMap(message) => write!(f, "invalid map: {}", message),
+            Error::InvalidProject { path, message } => {
+                write!(f, "{}: invalid Rojo project: {}", path.display(), message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Module { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
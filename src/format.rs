@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use log::debug;
+
+use crate::{
+    error::ParseError,
+    minifier::{emitter::Emitter, header, scope::ScopeTree},
+    Error, RenameKind, RenameMap, RenamedName, Target,
+};
+
+/// How `format` writes code
+#[derive(Clone, Debug)]
+pub struct FormatOptions {
+    /// The indentation of each level of blocks
+    pub indent: String,
+    /// The Lua version the code is written in
+    pub dialect: Target,
+    /// The rename map written when the code was minified, to give variables their names back
+    pub rename_map: Option<RenameMap>,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            indent: String::from("\t"),
+            dialect: Target::Luau,
+            rename_map: None,
+        }
+    }
+}
+
+/// The original names of the variables of minified code, keyed by the byte offset of each
+/// reference. The rename map records where each renamed local is in the minified code, and a
+/// local gets its name back when one of its references is at such an offset with its name.
+fn original_names(scopes: &ScopeTree, rename_map: &RenameMap) -> HashMap<usize, String> {
+    let mut names = HashMap::new();
+    let locals: HashMap<usize, &RenamedName> = rename_map
+        .renames
+        .iter()
+        .filter(|name| name.kind == RenameKind::Local)
+        .flat_map(|name| name.offsets.iter().map(move |&offset| (offset, name)))
+        .collect();
+    for binding in &scopes.bindings {
+        let Some(local) = binding
+            .positions
+            .iter()
+            .filter_map(|position| locals.get(position))
+            .find(|local| local.renamed == binding.name)
+        else {
+            continue;
+        };
+        names.extend(
+            binding
+                .positions
+                .iter()
+                .map(|&position| (position, local.original.clone())),
+        );
+    }
+    // Mangled globals all have different names
+    let globals: HashMap<&str, &str> = rename_map
+        .renames
+        .iter()
+        .filter(|name| name.kind == RenameKind::Global)
+        .map(|name| (name.renamed.as_str(), name.original.as_str()))
+        .collect();
+    for global in &scopes.globals {
+        if let Some(original) = globals.get(global.name.as_str()) {
+            names.extend(
+                global
+                    .positions
+                    .iter()
+                    .map(|&position| (position, original.to_string())),
+            );
+        }
+    }
+    names
+}
+
+/// Writes a chunk back out readably, with a statement per line, indented blocks and spaces
+/// around operators. Comments are dropped, except for the ones the minifier keeps.
+pub fn format(source: &str, options: &FormatOptions) -> Result<String, Error> {
+    let ast = full_moon::parse_fallible(source, options.dialect.lua_version())
+        .into_result()
+        .map_err(|errors| Error::Parse(errors.into_iter().map(ParseError::from).collect()))?;
+    let names = match &options.rename_map {
+        Some(rename_map) => {
+            let scopes = ScopeTree::build(ast.nodes())?;
+            let names = original_names(&scopes, rename_map);
+            debug!("Restored the names of {} references", names.len());
+            names
+        }
+        None => HashMap::new(),
+    };
+    let mut emitter = Emitter::new(&names).pretty(&options.indent);
+    emitter.block(ast.nodes());
+    let mut code = header::header(&ast);
+    code.push_str(emitter.finish().trim_end());
+    code.push('\n');
+    Ok(code)
+}
@@ -0,0 +1,224 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use ignore::{overrides::OverrideBuilder, WalkBuilder};
+use log::{debug, warn};
+use luamine_rs::{Error, Target};
+use serde_json::Value;
+
+/// Extensions of the files picked up in directories
+const EXTENSIONS: &[&str] = &["lua", "luau"];
+
+/// lua-language-server's config, whose `runtime.version` names the Lua version of a project
+const LUARC: &str = ".luarc.json";
+/// Luau's config, which only Luau projects have
+const LUAURC: &str = ".luaurc";
+
+/// A file to minify
+#[derive(Debug)]
+pub struct Input {
+    pub path: PathBuf,
+    /// Where the file goes in the output directory: relative to the directory or the base of
+    /// the glob it was found with, or just its name for a file given as is
+    pub relative: PathBuf,
+}
+
+/// Which files to pick up in directories and globs
+pub struct Filters<'a> {
+    /// Gitignore style globs, relative to the directory or the base of the glob
+    pub excludes: &'a [String],
+    /// Skip the files ignored by `.gitignore` and `.ignore` files
+    pub respect_ignore: bool,
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '[', '{'])
+}
+
+fn invalid(pattern: &str, message: impl ToString) -> Error {
+    Error::io(
+        pattern,
+        io::Error::new(io::ErrorKind::InvalidInput, message.to_string()),
+    )
+}
+
+/// Splits a glob into the directory its literal components lead to and the rest
+fn split_glob(pattern: &str) -> (PathBuf, String) {
+    let mut base = PathBuf::new();
+    let mut components = Path::new(pattern).components().peekable();
+    while let Some(component) = components.next_if(|component| {
+        !matches!(component, Component::Normal(name) if is_glob(&name.to_string_lossy()))
+    }) {
+        base.push(component);
+    }
+    let rest: PathBuf = components.collect();
+    if base.as_os_str().is_empty() {
+        base.push(".");
+    }
+    (base, rest.to_string_lossy().replace('\\', "/"))
+}
+
+/// Walks `root` and returns the files the overrides and ignore files let through
+fn walk(
+    root: &Path,
+    pattern: &str,
+    glob: Option<&str>,
+    filters: &Filters,
+) -> Result<Vec<Input>, Error> {
+    let mut overrides = OverrideBuilder::new(root);
+    if let Some(glob) = glob {
+        overrides
+            .add(glob)
+            .map_err(|error| invalid(pattern, error))?;
+    }
+    for exclude in filters.excludes {
+        overrides
+            .add(&format!("!{}", exclude))
+            .map_err(|error| invalid(exclude, error))?;
+    }
+    let overrides = overrides.build().map_err(|error| invalid(pattern, error))?;
+    let mut inputs = Vec::new();
+    let walker = WalkBuilder::new(root)
+        .overrides(overrides)
+        .git_ignore(filters.respect_ignore)
+        .git_global(filters.respect_ignore)
+        .git_exclude(filters.respect_ignore)
+        .ignore(filters.respect_ignore)
+        .parents(filters.respect_ignore)
+        .require_git(false)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build();
+    for entry in walker {
+        let entry = entry.map_err(|error| invalid(pattern, error))?;
+        if !entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_file())
+        {
+            continue;
+        }
+        let path = entry.into_path();
+        // A glob picks the files itself
+        let wanted = glob.is_some()
+            || path
+                .extension()
+                .is_some_and(|extension| EXTENSIONS.iter().any(|wanted| extension == *wanted));
+        if wanted {
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+            inputs.push(Input { path, relative });
+        }
+    }
+    Ok(inputs)
+}
+
+/// Expands files, directories and glob patterns into the files to minify. Files are taken as
+/// is, directories are searched for Lua(u) files and globs for the files they match.
+pub fn collect(patterns: &[String], filters: &Filters) -> Result<Vec<Input>, Error> {
+    let mut inputs: Vec<Input> = Vec::new();
+    let mut seen = HashSet::new();
+    for pattern in patterns {
+        let path = Path::new(pattern);
+        let found = if path.is_dir() {
+            walk(path, pattern, None, filters)?
+        } else if path.is_file() {
+            let relative = PathBuf::from(path.file_name().unwrap_or(path.as_os_str()));
+            vec![Input {
+                path: path.to_path_buf(),
+                relative,
+            }]
+        } else if is_glob(pattern) {
+            let (base, glob) = split_glob(pattern);
+            walk(&base, pattern, Some(&glob), filters)?
+        } else {
+            return Err(Error::io(
+                pattern,
+                io::Error::new(io::ErrorKind::NotFound, "no such file or directory"),
+            ));
+        };
+        if found.is_empty() {
+            warn!("{} matches no files", pattern);
+        }
+        for input in found {
+            if seen.insert(input.path.clone()) {
+                inputs.push(input);
+            }
+        }
+    }
+    // Two inputs can't be written to the same place
+    let mut outputs: HashMap<&Path, &Path> = HashMap::new();
+    for input in &inputs {
+        if let Some(other) = outputs.insert(&input.relative, &input.path) {
+            return Err(invalid(
+                &input.path.to_string_lossy(),
+                format!("would be written to the same place as {}", other.display()),
+            ));
+        }
+    }
+    Ok(inputs)
+}
+
+/// The Lua version of a project's `.luarc.json`, if it names one
+fn luarc_version(path: &Path) -> Option<Target> {
+    let text = fs::read_to_string(path).ok()?;
+    let config: Value = match serde_json::from_str(&text) {
+        Ok(config) => config,
+        Err(error) => {
+            warn!("Ignoring {}: {}", path.display(), error);
+            return None;
+        }
+    };
+    // The key may also be written as a dotted path
+    let version = config
+        .pointer("/runtime/version")
+        .or_else(|| config.get("runtime.version"))?
+        .as_str()?;
+    match version {
+        "Lua 5.1" => Some(Target::Lua51),
+        "Lua 5.2" => Some(Target::Lua52),
+        "Lua 5.3" => Some(Target::Lua53),
+        "Lua 5.4" => Some(Target::Lua54),
+        "LuaJIT" => Some(Target::LuaJit),
+        _ => {
+            warn!(
+                "Ignoring unknown runtime.version {} in {}",
+                version,
+                path.display()
+            );
+            None
+        }
+    }
+}
+
+/// Detects the Lua version of a file: a `.luau` file is Luau, otherwise the closest
+/// `.luarc.json` naming a version or `.luaurc` in its directory or above decides
+pub fn detect_dialect(path: &Path) -> Option<Target> {
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "luau")
+    {
+        return Some(Target::Luau);
+    }
+    let directory = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty());
+    let directory = directory.unwrap_or(Path::new(".")).canonicalize().ok()?;
+    for directory in directory.ancestors() {
+        let luarc = directory.join(LUARC);
+        if let Some(version) = luarc_version(&luarc) {
+            debug!(
+                "{} is {} according to {}",
+                path.display(),
+                version,
+                luarc.display()
+            );
+            return Some(version);
+        }
+        if directory.join(LUAURC).is_file() {
+            debug!("{} is Luau according to {}", path.display(), LUAURC);
+            return Some(Target::Luau);
+        }
+    }
+    None
+}
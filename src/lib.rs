@@ -0,0 +1,92 @@
+//! An experimental Lua(u) minifier built using full-moon
+//!
+//! ```no_run
+//! let source = std::fs::read_to_string("script.lua").unwrap();
+//! let output = luamine_rs::minify(&source, &luamine_rs::Options::default()).unwrap();
+//! println!("{}", output.code);
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+pub use bundle::{bundle, Bundle, BundleOptions, BundledModule};
+pub use error::{Error, ParseError, Position};
+pub use format::{format, FormatOptions};
+pub use options::{Options, Target, TypeMode};
+pub use source_map::{Mapping, SourceMap};
+pub use unmap::{unmap, RenameMap, RenamedName};
+
+mod bundle;
+mod error;
+mod format;
+mod minifier;
+mod options;
+mod rojo;
+mod source_map;
+mod unmap;
+
+/// The result of minifying a chunk
+#[derive(Clone, Debug)]
+pub struct Output {
+    /// The minified code
+    pub code: String,
+    pub stats: Stats,
+    /// Every local and global which was given a new name
+    pub renames: Vec<Rename>,
+    /// Where each token of the code comes from, if `Options::source_map` is set
+    pub source_map: Option<SourceMap>,
+}
+
+/// Size statistics of a minified chunk
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+    /// Size of the source in bytes
+    pub input_size: usize,
+    /// Size of the minified code in bytes
+    pub output_size: usize,
+    pub renamed_locals: usize,
+    pub renamed_globals: usize,
+    /// Statements which were merged into the statement before them
+    pub merged_statements: usize,
+    /// Operators which were replaced by their value
+    pub folded_constants: usize,
+    /// Unreachable statements and dead branches which were removed
+    pub removed_statements: usize,
+}
+
+impl Stats {
+    /// Size of the minified code relative to the source
+    pub fn ratio(&self) -> f64 {
+        if self.input_size == 0 {
+            return 1.0;
+        }
+        self.output_size as f64 / self.input_size as f64
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RenameKind {
+    /// A local variable, function parameter or loop variable
+    Local,
+    /// A global mangled because of `Options::mangle_globals`
+    Global,
+}
+
+/// A name which was changed by the minifier
+#[derive(Clone, Debug)]
+pub struct Rename {
+    pub kind: RenameKind,
+    /// The name in the source
+    pub original: String,
+    /// The name in the minified code
+    pub renamed: String,
+    /// Byte offsets in the source of the declaration and every reference
+    pub positions: Vec<usize>,
+    /// Byte offsets in the minified code of the ones which are left after removing dead code
+    pub output_positions: Vec<usize>,
+}
+
+/// Minifies a Lua(u) chunk, running the passes selected by `options`
+pub fn minify(source: &str, options: &Options) -> Result<Output, Error> {
+    minifier::Minifier::new(source, options).minify()
+}
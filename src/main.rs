@@ -1,37 +1,642 @@
-use clap::Parser;
-use std::{fs::read_to_string, path::PathBuf};
-pub mod minifier;
+use clap::{error::ErrorKind, ArgAction, CommandFactory, Parser, Subcommand, ValueEnum};
+use inputs::{Filters, Input};
+use log::{info, warn, LevelFilter};
+use luamine_rs::{
+    BundleOptions, Error, FormatOptions, Options, Output, RenameMap, SourceMap, Stats, Target,
+    TypeMode,
+};
+use std::{
+    collections::HashSet,
+    fs::{self, read_to_string},
+    io::{self, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+mod inputs;
+
+/// The input which stands for stdin
+const STDIN: &str = "-";
+/// Exit code when the source doesn't parse, `EX_DATAERR` from sysexits.h
+const EXIT_PARSE_ERROR: u8 = 65;
+/// Exit code when a file can't be read or written, `EX_IOERR` from sysexits.h
+const EXIT_IO_ERROR: u8 = 74;
 
 /// An experimental Lua(u) minifier built using full-moon
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    subcommand_negates_reqs = true
+)]
 struct Args {
-    /// Path to lua file
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Lua(u) files, directories to search for .lua and .luau files, and glob patterns. Without
+    /// any or with `-`, the script is read from stdin and only the code is written to stdout.
+    #[arg(value_name = "INPUT")]
+    inputs: Vec<String>,
+    /// Path to lua file, same as giving it as an input
     #[arg(short, long)]
-    file: String,
+    file: Option<String>,
+    /// Path to the output file, if not provided, prints to stdout
+    #[arg(short, long, conflicts_with = "out_dir")]
+    output: Option<String>,
+    /// Write every minified file to this directory, mirroring the tree of each input
+    /// directory and glob
+    #[arg(long, value_name = "DIR")]
+    out_dir: Option<String>,
+    /// Gitignore style glob of the files to skip in input directories and globs, can be
+    /// repeated
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+    /// Don't skip the files ignored by .gitignore and .ignore files
+    #[arg(long)]
+    no_ignore: bool,
+    #[command(flatten)]
+    minify: MinifyArgs,
+    /// Write a source map of the output, which maps it back to the input file and its names
+    #[arg(long, value_name = "PATH", conflicts_with = "out_dir")]
+    source_map: Option<String>,
+    /// Append a `--# sourceMappingURL=<URL>` comment pointing at the source map
+    #[arg(long, value_name = "URL", requires = "source_map")]
+    source_map_url: Option<String>,
+    /// Write the renamed locals and globals as JSON, for `unmap`
+    #[arg(long, value_name = "PATH", conflicts_with = "out_dir")]
+    rename_map: Option<String>,
+    /// Log more details to stderr, repeat for even more (-vv, -vvv)
+    #[arg(short, long, global = true, action = ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+    /// Only log errors
+    #[arg(short, long, global = true)]
+    quiet: bool,
+}
+
+/// The options shared by minifying and bundling
+#[derive(clap::Args, Debug)]
+struct MinifyArgs {
+    /// Also rename globals assigned by the script, list globals used by other scripts in --keep-globals
+    #[arg(long)]
+    mangle_globals: bool,
+    /// Comma separated globals which keep their names when mangling globals
+    #[arg(long, value_delimiter = ',', requires = "mangle_globals")]
+    keep_globals: Vec<String>,
+    /// Re-parse the minified code and fail if it doesn't match the structure of the source
+    #[arg(long)]
+    verify: bool,
+    /// Keep Luau type syntax, or strip it to get plain Lua
+    #[arg(long, value_enum, default_value_t = Types::Keep)]
+    types: Types,
+    /// The Lua version the input is written in. A .luau file is Luau, otherwise the closest
+    /// .luarc.json naming a runtime.version or .luaurc decides, then --target, then Luau.
+    #[arg(long, value_enum, value_name = "DIALECT")]
+    input_dialect: Option<TargetArg>,
+    /// The Lua version the output runs on, it may only use the syntax that version has.
+    /// Defaults to the input dialect.
+    #[arg(long, value_enum)]
+    target: Option<TargetArg>,
+    /// Escape every character outside of ASCII in string literals, for environments which need
+    /// 7-bit output
+    #[arg(long)]
+    ascii_only: bool,
+    /// Text of the comment put above the minified code, after the shebang, Luau directives and
+    /// --[[! ... ]] license comments of the input which are always kept
+    #[arg(long, value_name = "TEXT")]
+    banner: Option<String>,
+    /// Don't put a comment above the minified code, which is the default for stdin
+    #[arg(long, conflicts_with = "banner")]
+    no_banner: bool,
+}
+
+impl MinifyArgs {
+    /// The options these arguments select, without a banner by default for stdin
+    fn options(&self, from_stdin: bool) -> Options {
+        Options {
+            mangle_globals: self.mangle_globals,
+            keep_globals: HashSet::from_iter(self.keep_globals.iter().cloned()),
+            verify: self.verify,
+            types: self.types.into(),
+            ascii_only: self.ascii_only,
+            banner: match (self.no_banner, &self.banner) {
+                (true, _) => None,
+                (false, Some(text)) => Some(text.clone()),
+                // The code alone, for pipelines
+                (false, None) if from_stdin => None,
+                (false, None) => Options::default().banner,
+            },
+            ..Options::default()
+        }
+    }
+
+    /// The options to minify the file at `path` with: its dialect comes from --input-dialect
+    /// or is detected, and the target defaults to it
+    fn file_options(&self, options: &Options, path: &Path) -> Options {
+        let target = self.target.map(Target::from);
+        let dialect = self
+            .input_dialect
+            .map(Target::from)
+            .or_else(|| inputs::detect_dialect(path))
+            .or(target)
+            .unwrap_or_default();
+        let target = target.unwrap_or(dialect);
+        info!("Reading {} as {}, for {}", path.display(), dialect, target);
+        Options {
+            dialect,
+            target,
+            ..options.clone()
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Rewrite a stack trace of minified code read from stdin in terms of the source
+    Unmap(UnmapArgs),
+    /// Inline the modules a script requires into one file, then minify it
+    Bundle(BundleArgs),
+    /// Write minified code back out readably, with the names of a rename map
+    Format(FormatArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct BundleArgs {
+    /// The script to run, whose requires of string literals are bundled
+    entry: String,
+    /// Rojo project file, to bundle requires of instances like `require(script.Parent.Util)`
+    #[arg(long, value_name = "PATH")]
+    rojo_project: Option<String>,
+    /// Path to the output file, if not provided, prints to stdout
+    #[arg(short, long)]
+    output: Option<String>,
+    /// Where to look for modules, like package.path: `?` stands for the module name with its
+    /// dots replaced by directory separators
+    #[arg(long, value_name = "TEMPLATES", default_value_t = BundleOptions::default().package_path)]
+    package_path: String,
+    /// Comma separated modules left to the require of the runtime, like C modules
+    #[arg(long, value_name = "MODULES", value_delimiter = ',')]
+    external: Vec<String>,
+    /// Write the bundle without minifying it
+    #[arg(long)]
+    no_minify: bool,
+    #[command(flatten)]
+    minify: MinifyArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct UnmapArgs {
+    /// Source map written by --source-map, to map `file:line:column` locations back
+    #[arg(long, value_name = "PATH", required_unless_present = "rename_map")]
+    source_map: Option<String>,
+    /// Rename map written by --rename-map, to restore the names of renamed variables
+    #[arg(long, value_name = "PATH")]
+    rename_map: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct FormatArgs {
+    /// The script to format, read from stdin when it's `-` or not given
+    input: Option<String>,
     /// Path to the output file, if not provided, prints to stdout
     #[arg(short, long)]
     output: Option<String>,
+    /// Rename map written by --rename-map when the code was minified, to restore the names of
+    /// renamed variables
+    #[arg(long, value_name = "PATH")]
+    rename_map: Option<String>,
+    /// Indent blocks with this many spaces instead of a tab
+    #[arg(long, value_name = "SPACES")]
+    indent: Option<usize>,
+    /// The Lua version the input is written in. A .luau file is Luau, otherwise the closest
+    /// .luarc.json naming a runtime.version or .luaurc decides, then Luau.
+    #[arg(long, value_enum, value_name = "DIALECT")]
+    input_dialect: Option<TargetArg>,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Types {
+    /// Minify type annotations, declarations and assertions
+    Keep,
+    /// Remove all type syntax
+    Strip,
+}
+
+impl From<Types> for TypeMode {
+    fn from(types: Types) -> Self {
+        match types {
+            Types::Keep => TypeMode::Keep,
+            Types::Strip => TypeMode::Strip,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum TargetArg {
+    Lua51,
+    Lua52,
+    Lua53,
+    Lua54,
+    Luajit,
+    Luau,
 }
 
-fn main() {
-    println!("!!! NOT READY FOR PRODUCTION USE !!!");
-    println!("Lumine is cute :3");
+impl From<TargetArg> for Target {
+    fn from(target: TargetArg) -> Self {
+        match target {
+            TargetArg::Lua51 => Target::Lua51,
+            TargetArg::Lua52 => Target::Lua52,
+            TargetArg::Lua53 => Target::Lua53,
+            TargetArg::Lua54 => Target::Lua54,
+            TargetArg::Luajit => Target::LuaJit,
+            TargetArg::Luau => Target::Luau,
+        }
+    }
+}
+
+fn main() -> ExitCode {
     let args = Args::parse();
-    println!("Reading file {}...", args.file);
-    let file = PathBuf::from(args.file);
-    let text = read_to_string(file).expect("read input file error");
-    let mut minifier = minifier::Minifier::new(&text);
-    let result = minifier.minify();
-    //println!("{:#?}", new_ast);
-    println!("\n=== SCRIPT GENERATED ===\n");
-    match args.output {
+    let level = match (args.quiet, args.verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Warn,
+        (false, 1) => LevelFilter::Info,
+        (false, 2) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .format_timestamp(None)
+        .init();
+    match args.command {
+        Some(Command::Unmap(unmap_args)) => return unmap(unmap_args),
+        Some(Command::Bundle(bundle_args)) => return bundle(bundle_args),
+        Some(Command::Format(format_args)) => return format(format_args),
+        None => {}
+    }
+    warn!("luamine is not ready for production use, check the output before shipping it");
+    let patterns: Vec<String> = args.file.iter().chain(&args.inputs).cloned().collect();
+    let from_stdin = patterns.is_empty() || patterns == [STDIN];
+    if patterns.len() > 1 && patterns.iter().any(|pattern| pattern == STDIN) {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "`-` reads stdin and can't be combined with other inputs",
+            )
+            .exit();
+    }
+    if from_stdin && args.out_dir.is_some() {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--out-dir needs input files, not stdin",
+            )
+            .exit();
+    }
+    if patterns.is_empty() && io::stdin().is_terminal() {
+        Args::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "no input, give files to minify or pipe a script to stdin",
+            )
+            .exit();
+    }
+    let options = Options {
+        source_map: args.source_map.is_some(),
+        ..args.minify.options(from_stdin)
+    };
+    if from_stdin {
+        let mut text = String::new();
+        if let Err(error) = io::stdin().read_to_string(&mut text) {
+            return report(&Error::io("<stdin>", error), "<stdin>", "");
+        }
+        // Stdin is read like a file of the current directory
+        let options = args.minify.file_options(&options, Path::new(STDIN));
+        return match minify_text("<stdin>", text, &options) {
+            Ok((text, output)) => minify_one("<stdin>", text, output, &args),
+            Err(code) => ExitCode::from(code),
+        };
+    }
+    let filters = Filters {
+        excludes: &args.exclude,
+        respect_ignore: !args.no_ignore,
+    };
+    let inputs = match inputs::collect(&patterns, &filters) {
+        Ok(inputs) => inputs,
+        Err(error) => return report(&error, "", ""),
+    };
+    match &args.out_dir {
+        Some(out_dir) => minify_all(&inputs, Path::new(out_dir), &options, &args),
+        None => match inputs.as_slice() {
+            [input] => {
+                let file = input.path.to_string_lossy();
+                let options = args.minify.file_options(&options, &input.path);
+                match minify_file(&file, &options) {
+                    Ok((text, output)) => minify_one(&file, text, output, &args),
+                    Err(code) => ExitCode::from(code),
+                }
+            }
+            _ => {
+                eprintln!(
+                    "error: {} files to minify, use --out-dir to write them all",
+                    inputs.len()
+                );
+                ExitCode::FAILURE
+            }
+        },
+    }
+}
+
+/// Minifies the source of `file`, printing a diagnostic and returning the exit code if that
+/// fails
+fn minify_text(file: &str, text: String, options: &Options) -> Result<(String, Output), u8> {
+    match luamine_rs::minify(&text, options) {
+        Ok(output) => Ok((text, output)),
+        Err(error) => {
+            report(&error, file, &text);
+            Err(exit_code(&error))
+        }
+    }
+}
+
+/// Reads and minifies a file, printing a diagnostic and returning the exit code if either fails
+fn minify_file(file: &str, options: &Options) -> Result<(String, Output), u8> {
+    info!("Reading file {}...", file);
+    match read_to_string(file) {
+        Ok(text) => minify_text(file, text, options),
+        Err(error) => {
+            let error = Error::io(file, error);
+            report(&error, file, "");
+            Err(exit_code(&error))
+        }
+    }
+}
+
+/// Writes the minified `file` and its maps where `args` asks for, stdout by default
+fn minify_one(file: &str, text: String, mut output: Output, args: &Args) -> ExitCode {
+    info!(
+        "Minified {} bytes to {} bytes ({:.1}%), renamed {} locals and {} globals, merged {} statements, folded {} constants, removed {} dead statements",
+        output.stats.input_size,
+        output.stats.output_size,
+        output.stats.ratio() * 100.0,
+        output.stats.renamed_locals,
+        output.stats.renamed_globals,
+        output.stats.merged_statements,
+        output.stats.folded_constants,
+        output.stats.removed_statements
+    );
+    if let (Some(map_path), Some(mut source_map)) = (&args.source_map, output.source_map.take()) {
+        source_map.file = args.output.clone();
+        source_map.sources = vec![file.to_string()];
+        if let Err(error) = fs::write(map_path, source_map.to_json()) {
+            return report(&Error::io(map_path, error), file, &text);
+        }
+        info!("Source map written to {}", map_path);
+    }
+    if let Some(map_path) = &args.rename_map {
+        let rename_map = RenameMap::new(&text, &output.renames);
+        if let Err(error) = fs::write(map_path, rename_map.to_json()) {
+            return report(&Error::io(map_path, error), file, &text);
+        }
+        info!("Rename map written to {}", map_path);
+    }
+    if let Some(url) = &args.source_map_url {
+        output
+            .code
+            .push_str(&format!("\n--# sourceMappingURL={}", url));
+    }
+    match &args.output {
+        Some(output_path) => {
+            if let Err(error) = fs::write(output_path, output.code) {
+                return report(&Error::io(output_path, error), file, &text);
+            }
+            info!("Written to {}", output_path);
+        }
+        None => {
+            output.code.push('\n');
+            if let Err(error) = write_stdout(&output.code) {
+                return report(&error, file, &text);
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Minifies every input into `out_dir`, then prints the size of each file and the total
+fn minify_all(inputs: &[Input], out_dir: &Path, options: &Options, args: &Args) -> ExitCode {
+    let mut results: Vec<(&Input, Result<Stats, u8>)> = Vec::new();
+    for input in inputs {
+        let file = input.path.to_string_lossy();
+        let options = args.minify.file_options(options, &input.path);
+        let stats = minify_file(&file, &options).and_then(|(text, output)| {
+            let output_path = out_dir.join(&input.relative);
+            let written = output_path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(&output_path, &output.code));
+            match written {
+                Ok(()) => {
+                    info!("Written to {}", output_path.display());
+                    Ok(output.stats)
+                }
+                Err(error) => {
+                    let error = Error::io(output_path, error);
+                    report(&error, &file, &text);
+                    Err(exit_code(&error))
+                }
+            }
+        });
+        results.push((input, stats));
+    }
+    let failed = results.iter().filter(|(_, stats)| stats.is_err()).count();
+    if !args.quiet {
+        let width = results
+            .iter()
+            .map(|(input, _)| input.path.to_string_lossy().len())
+            .max()
+            .unwrap_or_default();
+        let mut total = Stats::default();
+        for (input, stats) in &results {
+            let path = input.path.to_string_lossy();
+            match stats {
+                Ok(stats) => {
+                    eprintln!(
+                        "{:width$}  {:>9} -> {:>9} bytes ({:.1}%)",
+                        path,
+                        stats.input_size,
+                        stats.output_size,
+                        stats.ratio() * 100.0
+                    );
+                    total.input_size += stats.input_size;
+                    total.output_size += stats.output_size;
+                }
+                Err(_) => eprintln!("{:width$}  failed", path),
+            }
+        }
+        eprintln!(
+            "{} files minified, {} failed: {} -> {} bytes ({:.1}%)",
+            results.len() - failed,
+            failed,
+            total.input_size,
+            total.output_size,
+            total.ratio() * 100.0
+        );
+    }
+    // I/O errors take precedence over parse errors, which take precedence over the rest
+    let code = results
+        .iter()
+        .filter_map(|(_, stats)| stats.err())
+        .max()
+        .unwrap_or(0);
+    ExitCode::from(code)
+}
+
+/// Runs the `bundle` subcommand
+fn bundle(args: BundleArgs) -> ExitCode {
+    warn!("luamine is not ready for production use, check the output before shipping it");
+    let entry = Path::new(&args.entry);
+    let options = args.minify.file_options(&args.minify.options(false), entry);
+    let bundle_options = BundleOptions {
+        package_path: args.package_path.clone(),
+        externals: HashSet::from_iter(args.external.iter().cloned()),
+        dialect: options.dialect,
+        rojo_project: args.rojo_project.as_ref().map(PathBuf::from),
+    };
+    let bundle = match luamine_rs::bundle(entry, &bundle_options) {
+        Ok(bundle) => bundle,
+        Err(error) => return report(&error, &args.entry, ""),
+    };
+    for module in &bundle.modules {
+        info!("Bundled {} from {}", module.name, module.path.display());
+    }
+    let mut code = match args.no_minify {
+        true => bundle.code,
+        false => match minify_text("<bundle>", bundle.code, &options) {
+            Ok((_, output)) => output.code,
+            Err(code) => return ExitCode::from(code),
+        },
+    };
+    match &args.output {
         Some(output_path) => {
-            std::fs::write(output_path, result).expect("write output file error");
-            println!("Written to output file.");
+            if let Err(error) = fs::write(output_path, code) {
+                return report(&Error::io(output_path, error), &args.entry, "");
+            }
+            info!("Written to {}", output_path);
         }
         None => {
-            println!("{}", result);
+            code.push('\n');
+            if let Err(error) = write_stdout(&code) {
+                return report(&error, &args.entry, "");
+            }
         }
     }
+    ExitCode::SUCCESS
+}
+
+/// Runs the `unmap` subcommand
+fn unmap(args: UnmapArgs) -> ExitCode {
+    let source_map = match &args.source_map {
+        Some(path) => match read_to_string(path) {
+            Ok(json) => match SourceMap::from_json(&json) {
+                Ok(source_map) => Some(source_map),
+                Err(error) => return report(&error, path, &json),
+            },
+            Err(error) => return report(&Error::io(path, error), path, ""),
+        },
+        None => None,
+    };
+    let rename_map = match args.rename_map.as_deref().map(read_rename_map).transpose() {
+        Ok(rename_map) => rename_map,
+        Err(code) => return code,
+    };
+    let mut trace = String::new();
+    if let Err(error) = io::stdin().read_to_string(&mut trace) {
+        return report(&Error::io("<stdin>", error), "<stdin>", "");
+    }
+    let unmapped = luamine_rs::unmap(&trace, source_map.as_ref(), rename_map.as_ref());
+    match write_stdout(&unmapped) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => report(&error, "<stdout>", ""),
+    }
+}
+
+/// Runs the `format` subcommand
+fn format(args: FormatArgs) -> ExitCode {
+    let rename_map = match args.rename_map.as_deref().map(read_rename_map).transpose() {
+        Ok(rename_map) => rename_map,
+        Err(code) => return code,
+    };
+    let input = args.input.as_deref().unwrap_or(STDIN);
+    let (file, text) = if input == STDIN {
+        let mut text = String::new();
+        if let Err(error) = io::stdin().read_to_string(&mut text) {
+            return report(&Error::io("<stdin>", error), "<stdin>", "");
+        }
+        ("<stdin>", text)
+    } else {
+        match read_to_string(input) {
+            Ok(text) => (input, text),
+            Err(error) => return report(&Error::io(input, error), input, ""),
+        }
+    };
+    // Stdin is read like a file of the current directory
+    let dialect = args
+        .input_dialect
+        .map(Target::from)
+        .or_else(|| inputs::detect_dialect(Path::new(input)))
+        .unwrap_or_default();
+    info!("Reading {} as {}", file, dialect);
+    let options = FormatOptions {
+        indent: match args.indent {
+            Some(spaces) => " ".repeat(spaces),
+            None => FormatOptions::default().indent,
+        },
+        dialect,
+        rename_map,
+    };
+    let code = match luamine_rs::format(&text, &options) {
+        Ok(code) => code,
+        Err(error) => return report(&error, file, &text),
+    };
+    let written = match &args.output {
+        Some(output_path) => {
+            fs::write(output_path, code).map_err(|error| Error::io(output_path, error))
+        }
+        None => write_stdout(&code),
+    };
+    match written {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => report(&error, file, ""),
+    }
+}
+
+/// Reads a rename map written by --rename-map, printing a diagnostic and returning the exit code
+/// if that fails
+fn read_rename_map(path: &str) -> Result<RenameMap, ExitCode> {
+    match read_to_string(path) {
+        Ok(json) => RenameMap::from_json(&json).map_err(|error| report(&error, path, &json)),
+        Err(error) => Err(report(&Error::io(path, error), path, "")),
+    }
+}
+
+/// Prints `error` as a diagnostic for the file at `path`
+fn report(error: &Error, path: &str, source: &str) -> ExitCode {
+    eprint!("{}", error.diagnostic(path, source));
+    ExitCode::from(exit_code(error))
+}
+
+fn exit_code(error: &Error) -> u8 {
+    match error {
+        Error::Module { error, .. } => exit_code(error),
+        Error::Parse(_) => EXIT_PARSE_ERROR,
+        Error::Io { .. } => EXIT_IO_ERROR,
+        _ => 1,
+    }
+}
+
+fn write_stdout(text: &str) -> Result<(), Error> {
+    let mut stdout = io::stdout().lock();
+    stdout
+        .write_all(text.as_bytes())
+        .and_then(|_| stdout.flush())
+        .map_err(|error| Error::io("<stdout>", error))
 }
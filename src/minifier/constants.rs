@@ -1,10 +1,32 @@
-use full_moon::{ast::Expression, tokenizer::TokenReference};
+use full_moon::{
+    ast::Expression,
+    tokenizer::{Symbol, TokenReference},
+};
 use std::sync::LazyLock;
 
+use crate::minifier::utils::symbol;
+
 pub static EQ_TOKEN: LazyLock<Option<TokenReference>> =
-    LazyLock::new(|| Some(TokenReference::symbol("=").unwrap()));
+    LazyLock::new(|| Some(symbol(Symbol::Equal)));
 pub static NIL_SYMBOL: LazyLock<Expression> =
-    LazyLock::new(|| Expression::Symbol(TokenReference::symbol("nil").unwrap()));
-pub static COMMA: LazyLock<TokenReference> = LazyLock::new(|| TokenReference::symbol(",").unwrap());
-pub static SEMICOLON: LazyLock<Option<TokenReference>> =
-    LazyLock::new(|| Some(TokenReference::symbol(";").unwrap()));
+    LazyLock::new(|| Expression::Symbol(symbol(Symbol::Nil)));
+pub static COMMA: LazyLock<TokenReference> = LazyLock::new(|| symbol(Symbol::Comma));
+
+/// Globals provided by the standard libraries of every supported dialect and by Roblox
+#[rustfmt::skip]
+pub const BUILTIN_GLOBALS: &[&str] = &[
+    "_G", "_ENV", "_VERSION", "assert", "bit", "bit32", "buffer", "collectgarbage", "coroutine",
+    "debug", "dofile", "error", "gcinfo", "getfenv", "getmetatable", "io", "ipairs", "jit",
+    "load", "loadfile", "loadstring", "math", "module", "newproxy", "next", "os", "package",
+    "pairs", "pcall", "print", "rawequal", "rawget", "rawlen", "rawset", "require", "select",
+    "setfenv", "setmetatable", "string", "table", "tonumber", "tostring", "type", "typeof",
+    "unpack", "utf8", "vector", "xpcall",
+    // Roblox
+    "Axes", "BrickColor", "CFrame", "Color3", "ColorSequence", "ColorSequenceKeypoint",
+    "DateTime", "DockWidgetPluginGuiInfo", "Enum", "Faces", "Font", "Instance", "NumberRange",
+    "NumberSequence", "NumberSequenceKeypoint", "OverlapParams", "PathWaypoint",
+    "PhysicalProperties", "Random", "Ray", "RaycastParams", "Rect", "Region3", "Region3int16",
+    "SharedTable", "TweenInfo", "UDim", "UDim2", "UserSettings", "Vector2", "Vector2int16",
+    "Vector3", "Vector3int16", "delay", "elapsedTime", "game", "plugin", "script", "settings",
+    "shared", "spawn", "task", "tick", "time", "version", "wait", "warn", "workspace",
+];
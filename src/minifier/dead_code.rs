@@ -0,0 +1,191 @@
+use full_moon::{
+    ast::{Block, Do, ElseIf, Expression, If, Stmt},
+    tokenizer::Symbol,
+};
+use log::trace;
+
+use crate::{
+    minifier::{fold::evaluate, utils::symbol},
+    Target,
+};
+
+/// What a statement turns into once its dead branches are removed
+enum Simplified {
+    Keep(Box<Stmt>),
+    Remove,
+    /// The statements of a block which always runs
+    Inline(Box<Block>),
+}
+
+/// Removes the statements of a block which can never run, and replaces conditionals whose
+/// condition is a constant by the branch which runs. Nested blocks must already have been
+/// simplified. Returns the new block and how many statements were removed.
+pub fn eliminate(block: Block, target: Target) -> (Block, usize) {
+    let mut stmts = Vec::new();
+    let mut last_stmt = block.last_stmt_with_semicolon().cloned();
+    let mut removed = 0;
+    // Set once a statement always leaves the block, which makes the rest of it unreachable
+    let mut dead = false;
+    // A `goto` can jump past the statement which leaves a block with labels
+    let labels = has_labels(&block);
+    for (stmt, _) in block.stmts_with_semicolon() {
+        if dead {
+            trace!("Removed unreachable statement: {}", stmt.to_string().trim());
+            removed += 1;
+            continue;
+        }
+        match simplify(stmt, target) {
+            Simplified::Keep(stmt) => {
+                dead = !labels && always_exits(&stmt);
+                stmts.push((*stmt, None));
+            }
+            Simplified::Remove => {
+                trace!("Removed dead statement: {}", stmt.to_string().trim());
+                removed += 1;
+            }
+            Simplified::Inline(inner) if labels || declares_names(&inner) => {
+                // Keep the locals and labels of the branch in their own scope
+                dead = !labels && always_exits_block(&inner);
+                stmts.push((
+                    Stmt::Do(
+                        Do::new()
+                            .with_do_token(symbol(Symbol::Do))
+                            .with_block(*inner)
+                            .with_end_token(symbol(Symbol::End)),
+                    ),
+                    None,
+                ));
+            }
+            Simplified::Inline(inner) => {
+                stmts.extend(inner.stmts().map(|stmt| (stmt.clone(), None)));
+                dead = inner.stmts().any(always_exits);
+                if let Some(inner_last_stmt) = inner.last_stmt_with_semicolon() {
+                    // The branch's `return` or `break` replaces the one of the block
+                    if let Some((old_last_stmt, _)) = last_stmt.replace(inner_last_stmt.clone()) {
+                        trace!(
+                            "Removed unreachable statement: {}",
+                            old_last_stmt.to_string().trim()
+                        );
+                        removed += 1;
+                    }
+                    dead = true;
+                    continue;
+                }
+            }
+        }
+        if dead {
+            if let Some((old_last_stmt, _)) = last_stmt.take() {
+                trace!(
+                    "Removed unreachable statement: {}",
+                    old_last_stmt.to_string().trim()
+                );
+                removed += 1;
+            }
+        }
+    }
+    (block.with_stmts(stmts).with_last_stmt(last_stmt), removed)
+}
+
+fn condition(expression: &Expression, target: Target) -> Option<bool> {
+    evaluate(expression, target).map(|value| value.is_truthy())
+}
+
+fn simplify(stmt: &Stmt, target: Target) -> Simplified {
+    match stmt {
+        Stmt::While(x) if condition(x.condition(), target) == Some(false) => Simplified::Remove,
+        Stmt::If(x) => simplify_if(x, target),
+        _ => Simplified::Keep(Box::new(stmt.clone())),
+    }
+}
+
+fn simplify_if(x: &If, target: Target) -> Simplified {
+    let mut branches = vec![(x.condition(), x.block(), None)];
+    branches.extend(
+        x.else_if()
+            .into_iter()
+            .flatten()
+            .map(|else_if| (else_if.condition(), else_if.block(), Some(else_if))),
+    );
+    let mut kept: Vec<(&Expression, &Block, Option<&ElseIf>)> = Vec::new();
+    let mut else_block = x.else_block();
+    let mut changed = false;
+    for (expression, block, else_if) in branches {
+        match condition(expression, target) {
+            Some(false) => changed = true,
+            Some(true) => {
+                // Later branches can never run
+                changed = true;
+                else_block = Some(block);
+                break;
+            }
+            None => kept.push((expression, block, else_if)),
+        }
+    }
+    if !changed {
+        return Simplified::Keep(Box::new(Stmt::If(x.clone())));
+    }
+    let Some(((expression, block, _), else_ifs)) = kept.split_first() else {
+        return match else_block {
+            Some(block) => Simplified::Inline(Box::new(block.clone())),
+            None => Simplified::Remove,
+        };
+    };
+    // Only the first branch can be the `if` itself
+    let else_ifs: Vec<ElseIf> = else_ifs
+        .iter()
+        .filter_map(|(_, _, else_if)| else_if.cloned())
+        .collect();
+    let else_token = x
+        .else_token()
+        .cloned()
+        .or_else(|| else_block.map(|_| symbol(Symbol::Else)));
+    Simplified::Keep(Box::new(Stmt::If(
+        x.clone()
+            .with_condition((*expression).clone())
+            .with_block((*block).clone())
+            .with_else_if((!else_ifs.is_empty()).then_some(else_ifs))
+            .with_else_token(else_block.and(else_token))
+            .with_else(else_block.cloned()),
+    )))
+}
+
+/// Whether the statements after `stmt` in its block can never run
+fn always_exits(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Do(x) => always_exits_block(x.block()),
+        Stmt::If(x) => {
+            always_exits_block(x.block())
+                && x.else_if()
+                    .into_iter()
+                    .flatten()
+                    .all(|else_if| always_exits_block(else_if.block()))
+                && x.else_block().is_some_and(always_exits_block)
+        }
+        _ => false,
+    }
+}
+
+fn always_exits_block(block: &Block) -> bool {
+    block.last_stmt().is_some() || (!has_labels(block) && block.stmts().any(always_exits))
+}
+
+fn has_labels(block: &Block) -> bool {
+    block.stmts().any(|stmt| matches!(stmt, Stmt::Label(_)))
+}
+
+/// Whether a block declares locals, types or labels which would be visible after it if it
+/// was inlined
+fn declares_names(block: &Block) -> bool {
+    block.stmts().any(|stmt| {
+        matches!(
+            stmt,
+            Stmt::LocalAssignment(_)
+                | Stmt::LocalFunction(_)
+                | Stmt::TypeDeclaration(_)
+                | Stmt::ExportedTypeDeclaration(_)
+                | Stmt::TypeFunction(_)
+                | Stmt::ExportedTypeFunction(_)
+                | Stmt::Label(_)
+        )
+    })
+}
@@ -0,0 +1,687 @@
+use std::{collections::HashMap, iter};
+
+use full_moon::{
+    self,
+    ast::{
+        luau::{TypeDeclaration, TypeSpecifier},
+        punctuated::Punctuated,
+        Block, Call, Expression, Field, FunctionArgs, FunctionBody, FunctionCall, FunctionName,
+        Index, LastStmt, Prefix, Stmt, Suffix, TableConstructor, UnOp, Var,
+    },
+    node::Node,
+    tokenizer::{StringLiteralQuoteType, TokenReference, TokenType},
+};
+
+use crate::{minifier::literals::escape_non_ascii, Target};
+
+/// Pairs of characters which would lex as a different token if nothing separated them
+const JOINED_SYMBOLS: &[(char, char)] = &[
+    ('.', '.'),
+    ('=', '='),
+    ('~', '='),
+    ('<', '='),
+    ('>', '='),
+    ('/', '/'),
+    (':', ':'),
+    ('<', '<'),
+    ('>', '>'),
+    ('-', '>'),
+    ('+', '='),
+    ('-', '='),
+    ('*', '='),
+    ('/', '='),
+    ('%', '='),
+    ('^', '='),
+    ('-', '-'),
+    ('[', '['),
+    ('[', '='),
+];
+
+/// Where a token of the output comes from
+pub struct Origin {
+    /// Byte offset of the token in the output
+    pub output: usize,
+    /// Byte offset of the token in the source
+    pub source: usize,
+    /// The name in the source of a renamed identifier
+    pub name: Option<String>,
+}
+
+/// Writes an AST back out as source code, without any trivia and with a space only where
+/// two tokens would otherwise run together. Pretty printing writes the same tokens with a
+/// statement per line, indented blocks and spaces around operators.
+pub struct Emitter<'a> {
+    /// Minified names keyed by the byte offset of each renamed identifier token
+    renames: &'a HashMap<usize, String>,
+    output: String,
+    /// A number directly followed by `.` or a name lexes as a malformed number
+    after_number: bool,
+    /// Escape the characters outside of ASCII in the string literals we write as is, the way
+    /// this target reads them back
+    ascii_only: Option<Target>,
+    /// The origin of every token which comes from the source, if they are tracked
+    origins: Option<Vec<Origin>>,
+    /// The indentation of each level of blocks, only when pretty printing
+    indent: Option<String>,
+    depth: usize,
+}
+
+impl<'a> Emitter<'a> {
+    pub fn new(renames: &'a HashMap<usize, String>) -> Self {
+        Emitter {
+            renames,
+            output: String::new(),
+            after_number: false,
+            ascii_only: None,
+            origins: None,
+            indent: None,
+            depth: 0,
+        }
+    }
+
+    pub fn ascii_only(self, ascii_only: Option<Target>) -> Self {
+        Emitter { ascii_only, ..self }
+    }
+
+    /// Records where each token comes from, for source maps
+    pub fn track_origins(self) -> Self {
+        Emitter {
+            origins: Some(Vec::new()),
+            ..self
+        }
+    }
+
+    /// Writes readable code rather than minified code
+    pub fn pretty(self, indent: &str) -> Self {
+        Emitter {
+            indent: Some(indent.to_string()),
+            ..self
+        }
+    }
+
+    pub fn finish(self) -> String {
+        self.output
+    }
+
+    pub fn finish_with_origins(self) -> (String, Vec<Origin>) {
+        (self.output, self.origins.unwrap_or_default())
+    }
+
+    fn needs_space(&self, text: &str) -> bool {
+        let (Some(last), Some(next)) = (self.output.chars().last(), text.chars().next()) else {
+            return false;
+        };
+        let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+        (is_word(last) && is_word(next))
+            || (self.after_number && (next == '.' || is_word(next)))
+            || JOINED_SYMBOLS.contains(&(last, next))
+    }
+
+    fn push(&mut self, text: &str) {
+        if self.needs_space(text) {
+            self.output.push(' ');
+        }
+        self.output.push_str(text);
+        self.after_number = false;
+    }
+
+    /// Separates the next token by a space when pretty printing
+    fn space(&mut self) {
+        if self.indent.is_some() && !self.output.is_empty() && !self.output.ends_with([' ', '\n']) {
+            self.output.push(' ');
+            self.after_number = false;
+        }
+    }
+
+    /// Starts a new line at the indentation of the current block when pretty printing
+    fn line(&mut self) {
+        let Some(indent) = &self.indent else {
+            return;
+        };
+        self.output
+            .truncate(self.output.trim_end_matches(' ').len());
+        if !self.output.is_empty() {
+            self.output.push('\n');
+            self.output.push_str(&indent.repeat(self.depth));
+        }
+        self.after_number = false;
+    }
+
+    /// Writes a token with a space on both sides when pretty printing
+    fn spaced(&mut self, token: &TokenReference) {
+        self.space();
+        self.token(token);
+        self.space();
+    }
+
+    /// Writes `=` with a space on both sides when pretty printing
+    fn equals(&mut self) {
+        self.space();
+        self.push("=");
+        self.space();
+    }
+
+    fn token(&mut self, token: &TokenReference) {
+        let token = token.token();
+        let renamed = match token.token_type() {
+            // Tokens we created ourselves have no position, so they can't be looked up
+            TokenType::Identifier { .. } if token.start_position().line() != 0 => {
+                self.renames.get(&token.start_position().bytes())
+            }
+            _ => None,
+        };
+        let text = match (renamed, token.token_type()) {
+            (Some(name), _) => name.clone(),
+            (
+                None,
+                TokenType::InterpolatedString { .. }
+                | TokenType::StringLiteral {
+                    quote_type: StringLiteralQuoteType::Double | StringLiteralQuoteType::Single,
+                    ..
+                },
+            ) => match self.ascii_only {
+                Some(target) => escape_non_ascii(&token.to_string(), target),
+                None => token.to_string(),
+            },
+            (None, _) => token.to_string(),
+        };
+        self.push(&text);
+        if let Some(origins) = &mut self.origins {
+            if token.start_position().line() != 0 {
+                origins.push(Origin {
+                    output: self.output.len() - text.len(),
+                    source: token.start_position().bytes(),
+                    name: renamed.map(|_| token.to_string()),
+                });
+            }
+        }
+        self.after_number = matches!(token.token_type(), TokenType::Number { .. });
+    }
+
+    /// Writes every token of a node as is
+    fn node(&mut self, node: &impl Node) {
+        // `Node::tokens` yields both tokens of a contained span before its contents
+        let mut tokens: Vec<&TokenReference> = node.tokens().collect();
+        tokens.sort_by_key(|token| token.token().start_position().bytes());
+        for token in tokens {
+            self.token(token);
+        }
+    }
+
+    fn list<T>(&mut self, items: &Punctuated<T>, mut write: impl FnMut(&mut Self, &T)) {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                self.push(",");
+                self.space();
+            }
+            write(self, item);
+        }
+    }
+
+    fn type_specifier(&mut self, type_specifier: &TypeSpecifier) {
+        self.token(type_specifier.punctuation());
+        self.space();
+        self.node(type_specifier.type_info());
+    }
+
+    pub fn block(&mut self, block: &Block) {
+        for (i, stmt) in block.stmts().enumerate() {
+            // `a=b` followed by `(c)()` would be read as `a=b(c)()`
+            if i > 0
+                && stmt
+                    .tokens()
+                    .next()
+                    .is_some_and(|token| token.to_string() == "(")
+            {
+                self.push(";");
+            }
+            self.line();
+            self.stmt(stmt);
+        }
+        if let Some(last_stmt) = block.last_stmt() {
+            self.line();
+            self.last_stmt(last_stmt);
+        }
+    }
+
+    /// Writes a block one level deeper than the statement it belongs to, followed by the
+    /// separator of the token which closes it
+    fn nested_block(&mut self, block: &Block) {
+        self.depth += 1;
+        self.block(block);
+        self.depth -= 1;
+        if block.stmts().next().is_some() || block.last_stmt().is_some() {
+            self.line();
+        } else {
+            self.space();
+        }
+    }
+
+    pub fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::LocalAssignment(x) => {
+                self.token(x.local_token());
+                // Only Lua 5.4 has attributes, and only Luau has types
+                let attributes = x.attributes().chain(std::iter::repeat(None));
+                for (i, ((name, attribute), type_specifier)) in x
+                    .names()
+                    .iter()
+                    .zip(attributes)
+                    .zip(x.type_specifiers())
+                    .enumerate()
+                {
+                    if i > 0 {
+                        self.push(",");
+                        self.space();
+                    }
+                    self.token(name);
+                    if let Some(attribute) = attribute {
+                        self.space();
+                        self.node(attribute);
+                    }
+                    if let Some(type_specifier) = type_specifier {
+                        self.type_specifier(type_specifier);
+                    }
+                }
+                if !x.expressions().is_empty() {
+                    self.equals();
+                    self.list(x.expressions(), Self::expression);
+                }
+            }
+            Stmt::Assignment(x) => {
+                self.list(x.variables(), Self::var);
+                self.equals();
+                self.list(x.expressions(), Self::expression);
+            }
+            Stmt::FunctionCall(x) => self.function_call(x),
+            Stmt::Do(x) => {
+                self.token(x.do_token());
+                self.nested_block(x.block());
+                self.token(x.end_token());
+            }
+            Stmt::While(x) => {
+                self.token(x.while_token());
+                self.space();
+                self.expression(x.condition());
+                self.space();
+                self.token(x.do_token());
+                self.nested_block(x.block());
+                self.token(x.end_token());
+            }
+            Stmt::Repeat(x) => {
+                self.token(x.repeat_token());
+                self.nested_block(x.block());
+                self.token(x.until_token());
+                self.space();
+                self.expression(x.until());
+            }
+            Stmt::If(x) => {
+                self.token(x.if_token());
+                self.space();
+                self.expression(x.condition());
+                self.space();
+                self.token(x.then_token());
+                self.nested_block(x.block());
+                for else_if in x.else_if().into_iter().flatten() {
+                    self.token(else_if.else_if_token());
+                    self.space();
+                    self.expression(else_if.condition());
+                    self.space();
+                    self.token(else_if.then_token());
+                    self.nested_block(else_if.block());
+                }
+                if let (Some(else_token), Some(block)) = (x.else_token(), x.else_block()) {
+                    self.token(else_token);
+                    self.nested_block(block);
+                }
+                self.token(x.end_token());
+            }
+            Stmt::NumericFor(x) => {
+                self.token(x.for_token());
+                self.token(x.index_variable());
+                if let Some(type_specifier) = x.type_specifier() {
+                    self.type_specifier(type_specifier);
+                }
+                self.equals();
+                self.expression(x.start());
+                self.push(",");
+                self.space();
+                self.expression(x.end());
+                if let Some(step) = x.step() {
+                    self.push(",");
+                    self.space();
+                    self.expression(step);
+                }
+                self.space();
+                self.token(x.do_token());
+                self.nested_block(x.block());
+                self.token(x.end_token());
+            }
+            Stmt::GenericFor(x) => {
+                self.token(x.for_token());
+                for (i, (name, type_specifier)) in
+                    x.names().iter().zip(x.type_specifiers()).enumerate()
+                {
+                    if i > 0 {
+                        self.push(",");
+                        self.space();
+                    }
+                    self.token(name);
+                    if let Some(type_specifier) = type_specifier {
+                        self.type_specifier(type_specifier);
+                    }
+                }
+                self.spaced(x.in_token());
+                self.list(x.expressions(), Self::expression);
+                self.space();
+                self.token(x.do_token());
+                self.nested_block(x.block());
+                self.token(x.end_token());
+            }
+            Stmt::FunctionDeclaration(x) => {
+                for attribute in x.attributes() {
+                    self.node(attribute);
+                    self.space();
+                }
+                self.token(x.function_token());
+                self.function_name(x.name());
+                self.function_body(x.body());
+            }
+            Stmt::LocalFunction(x) => {
+                for attribute in x.attributes() {
+                    self.node(attribute);
+                    self.space();
+                }
+                self.token(x.local_token());
+                self.token(x.function_token());
+                self.token(x.name());
+                self.function_body(x.body());
+            }
+            Stmt::CompoundAssignment(x) => {
+                self.var(x.lhs());
+                self.space();
+                self.node(x.compound_operator());
+                self.space();
+                self.expression(x.rhs());
+            }
+            Stmt::TypeDeclaration(x) => self.type_declaration(x),
+            Stmt::ExportedTypeDeclaration(x) => {
+                self.token(x.export_token());
+                self.type_declaration(x.type_declaration());
+            }
+            // Type functions and anything else we don't transform
+            _ => self.node(stmt),
+        }
+    }
+
+    fn type_declaration(&mut self, declaration: &TypeDeclaration) {
+        self.token(declaration.type_token());
+        self.token(declaration.type_name());
+        if let Some(generics) = declaration.generics() {
+            self.node(generics);
+        }
+        self.spaced(declaration.equal_token());
+        self.node(declaration.type_definition());
+    }
+
+    pub fn last_stmt(&mut self, last_stmt: &LastStmt) {
+        match last_stmt {
+            LastStmt::Return(x) => {
+                self.token(x.token());
+                self.space();
+                self.list(x.returns(), Self::expression);
+            }
+            _ => self.node(last_stmt),
+        }
+    }
+
+    fn function_name(&mut self, name: &FunctionName) {
+        for (i, part) in name.names().iter().enumerate() {
+            if i > 0 {
+                self.push(".");
+            }
+            self.token(part);
+        }
+        if let (Some(colon), Some(method)) = (name.method_colon(), name.method_name()) {
+            self.token(colon);
+            self.token(method);
+        }
+    }
+
+    fn function_body(&mut self, body: &FunctionBody) {
+        if let Some(generics) = body.generics() {
+            self.node(generics);
+        }
+        self.push("(");
+        // Lua versions keep no type specifier for `...`
+        let type_specifiers = body.type_specifiers().chain(iter::repeat(None));
+        for (i, (parameter, type_specifier)) in
+            body.parameters().iter().zip(type_specifiers).enumerate()
+        {
+            if i > 0 {
+                self.push(",");
+                self.space();
+            }
+            self.node(parameter);
+            if let Some(type_specifier) = type_specifier {
+                self.type_specifier(type_specifier);
+            }
+        }
+        self.push(")");
+        if let Some(return_type) = body.return_type() {
+            self.type_specifier(return_type);
+        }
+        self.nested_block(body.block());
+        self.token(body.end_token());
+    }
+
+    pub fn expression(&mut self, exp: &Expression) {
+        match exp {
+            Expression::BinaryOperator { lhs, binop, rhs } => {
+                self.expression(lhs);
+                self.spaced(binop.token());
+                self.expression(rhs);
+            }
+            Expression::UnaryOperator { unop, expression } => {
+                self.token(unop.token());
+                if let UnOp::Not(_) = unop {
+                    self.space();
+                }
+                self.expression(expression);
+            }
+            Expression::Parentheses {
+                contained,
+                expression,
+            } => {
+                let (open, close) = contained.tokens();
+                self.token(open);
+                self.expression(expression);
+                self.token(close);
+            }
+            Expression::Function(function) => {
+                for attribute in function.attributes() {
+                    self.node(attribute);
+                    self.space();
+                }
+                self.token(function.function_token());
+                self.function_body(function.body());
+            }
+            Expression::FunctionCall(call) => self.function_call(call),
+            Expression::IfExpression(x) => {
+                self.token(x.if_token());
+                self.space();
+                self.expression(x.condition());
+                self.spaced(x.then_token());
+                self.expression(x.if_expression());
+                for else_if in x.else_if_expressions().into_iter().flatten() {
+                    self.spaced(else_if.else_if_token());
+                    self.expression(else_if.condition());
+                    self.spaced(else_if.then_token());
+                    self.expression(else_if.expression());
+                }
+                self.spaced(x.else_token());
+                self.expression(x.else_expression());
+            }
+            Expression::InterpolatedString(x) => {
+                for segment in x.segments() {
+                    self.token(&segment.literal);
+                    let start = self.output.len();
+                    self.expression(&segment.expression);
+                    // `{{` isn't allowed inside an interpolated string
+                    if self.output[start..].starts_with('{') {
+                        self.output.insert(start, ' ');
+                        for origin in self.origins.iter_mut().flatten() {
+                            if origin.output >= start {
+                                origin.output += 1;
+                            }
+                        }
+                    }
+                }
+                self.token(x.last_string());
+            }
+            Expression::TableConstructor(table) => self.table(table),
+            Expression::TypeAssertion {
+                expression,
+                type_assertion,
+            } => {
+                self.expression(expression);
+                self.spaced(type_assertion.assertion_op());
+                self.node(type_assertion.cast_to());
+            }
+            Expression::Var(var) => self.var(var),
+            // Numbers, strings and symbols are single tokens
+            _ => self.node(exp),
+        }
+    }
+
+    fn table(&mut self, table: &TableConstructor) {
+        let (open, close) = table.braces().tokens();
+        self.token(open);
+        // Pretty printing puts a field per line when fields hold functions or other tables
+        let lines = self.indent.is_some()
+            && table.fields().iter().any(|field| match field {
+                Field::ExpressionKey { value, .. }
+                | Field::NameKey { value, .. }
+                | Field::NoKey(value) => match value {
+                    Expression::Function(_) => true,
+                    Expression::TableConstructor(table) => !table.fields().is_empty(),
+                    _ => false,
+                },
+                _ => false,
+            });
+        if !lines {
+            self.list(table.fields(), Self::field);
+            self.token(close);
+            return;
+        }
+        self.depth += 1;
+        for (i, field) in table.fields().iter().enumerate() {
+            if i > 0 {
+                self.push(",");
+            }
+            self.line();
+            self.field(field);
+        }
+        self.depth -= 1;
+        self.line();
+        self.token(close);
+    }
+
+    fn field(&mut self, field: &Field) {
+        match field {
+            Field::ExpressionKey {
+                brackets,
+                key,
+                equal,
+                value,
+            } => {
+                let (open, close) = brackets.tokens();
+                self.token(open);
+                self.expression(key);
+                self.token(close);
+                self.spaced(equal);
+                self.expression(value);
+            }
+            Field::NameKey { key, equal, value } => {
+                self.token(key);
+                self.spaced(equal);
+                self.expression(value);
+            }
+            Field::NoKey(value) => self.expression(value),
+            _ => self.node(field),
+        }
+    }
+
+    fn var(&mut self, var: &Var) {
+        match var {
+            Var::Name(name) => self.token(name),
+            Var::Expression(x) => {
+                self.prefix(x.prefix());
+                for suffix in x.suffixes() {
+                    self.suffix(suffix);
+                }
+            }
+            _ => self.node(var),
+        }
+    }
+
+    fn function_call(&mut self, call: &FunctionCall) {
+        self.prefix(call.prefix());
+        for suffix in call.suffixes() {
+            self.suffix(suffix);
+        }
+    }
+
+    fn prefix(&mut self, prefix: &Prefix) {
+        match prefix {
+            Prefix::Name(name) => self.token(name),
+            Prefix::Expression(exp) => self.expression(exp),
+            _ => self.node(prefix),
+        }
+    }
+
+    fn suffix(&mut self, suffix: &Suffix) {
+        match suffix {
+            Suffix::Call(Call::AnonymousCall(args)) => self.function_args(args),
+            Suffix::Call(Call::MethodCall(x)) => {
+                self.token(x.colon_token());
+                self.token(x.name());
+                if let Some(type_instantiation) = x.type_instantiation() {
+                    self.node(type_instantiation);
+                }
+                self.function_args(x.args());
+            }
+            Suffix::Index(Index::Brackets {
+                brackets,
+                expression,
+            }) => {
+                let (open, close) = brackets.tokens();
+                self.token(open);
+                self.expression(expression);
+                self.token(close);
+            }
+            Suffix::Index(Index::Dot { dot, name }) => {
+                self.token(dot);
+                self.token(name);
+            }
+            _ => self.node(suffix),
+        }
+    }
+
+    fn function_args(&mut self, args: &FunctionArgs) {
+        match args {
+            FunctionArgs::Parentheses {
+                parentheses,
+                arguments,
+            } => {
+                let (open, close) = parentheses.tokens();
+                self.token(open);
+                self.list(arguments, Self::expression);
+                self.token(close);
+            }
+            FunctionArgs::String(string) => self.token(string),
+            FunctionArgs::TableConstructor(table) => self.table(table),
+            _ => self.node(args),
+        }
+    }
+}
@@ -0,0 +1,392 @@
+use std::collections::HashMap;
+
+use full_moon::{
+    ast::{BinOp, Expression, UnOp},
+    tokenizer::{Symbol, TokenType},
+};
+use log::trace;
+
+use crate::{
+    minifier::{
+        emitter::Emitter,
+        literals::{decode_string, format_number, parse_number, string_literal, Number},
+        utils::{symbol, token},
+    },
+    Target,
+};
+
+/// Integers up to this size convert to floats and back exactly
+const MAX_EXACT_INTEGER: f64 = (1u64 << 53) as f64;
+
+/// A value which is known without running the program
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Nil,
+    Boolean(bool),
+    Number(Number),
+    String(Vec<u8>),
+}
+
+impl Value {
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Boolean(false))
+    }
+
+    /// Builds a literal for the value, `None` for numbers without one
+    pub fn to_expression(&self, target: Target) -> Option<Expression> {
+        Some(match self {
+            Value::Nil => Expression::Symbol(symbol(Symbol::Nil)),
+            Value::Boolean(true) => Expression::Symbol(symbol(Symbol::True)),
+            Value::Boolean(false) => Expression::Symbol(symbol(Symbol::False)),
+            Value::Number(number) => {
+                let magnitude = match *number {
+                    Number::Integer(integer) if integer < 0 => {
+                        Number::Integer(integer.checked_neg()?)
+                    }
+                    Number::Float(float) if float.is_sign_negative() => Number::Float(-float),
+                    number => number,
+                };
+                let literal = Expression::Number(token(TokenType::Number {
+                    text: format_number(magnitude, target)?.into(),
+                }));
+                if magnitude == *number {
+                    literal
+                } else {
+                    Expression::UnaryOperator {
+                        unop: UnOp::Minus(symbol(Symbol::Minus)),
+                        expression: Box::new(literal),
+                    }
+                }
+            }
+            Value::String(bytes) => Expression::String(token(string_literal(bytes, target, false))),
+        })
+    }
+}
+
+impl Number {
+    fn to_float(self) -> f64 {
+        match self {
+            Number::Integer(integer) => integer as f64,
+            Number::Float(float) => float,
+        }
+    }
+
+    /// The number as a float, `None` for integers which would be rounded
+    fn to_exact_float(self) -> Option<f64> {
+        match self {
+            Number::Integer(integer) if integer.unsigned_abs() > MAX_EXACT_INTEGER as u64 => None,
+            number => Some(number.to_float()),
+        }
+    }
+
+    /// What `tostring` gives, as far as every version of a target agrees on it
+    fn to_lua_string(self, target: Target) -> Option<String> {
+        match self {
+            Number::Integer(integer) => Some(integer.to_string()),
+            // Other floats are formatted with `%.14g` or the shortest round trip depending on
+            // the version
+            Number::Float(float) if float.fract() == 0.0 && float.abs() < 1e14 => {
+                let integer = (float as i64).to_string();
+                Some(if target.has_integers() {
+                    integer + ".0"
+                } else {
+                    integer
+                })
+            }
+            Number::Float(_) => None,
+        }
+    }
+}
+
+/// Evaluates an expression made of literals and operators, `None` if it depends on anything
+/// else, raises an error, or we can't be sure what the target gives for it
+pub fn evaluate(expression: &Expression, target: Target) -> Option<Value> {
+    match expression {
+        Expression::Number(token) => match token.token_type() {
+            TokenType::Number { text } => parse_number(text, target).map(Value::Number),
+            _ => None,
+        },
+        Expression::String(token) => decode_string(token.token(), target).map(Value::String),
+        Expression::Symbol(token) => match token.token_type() {
+            TokenType::Symbol {
+                symbol: Symbol::Nil,
+            } => Some(Value::Nil),
+            TokenType::Symbol {
+                symbol: Symbol::True,
+            } => Some(Value::Boolean(true)),
+            TokenType::Symbol {
+                symbol: Symbol::False,
+            } => Some(Value::Boolean(false)),
+            _ => None,
+        },
+        Expression::Parentheses { expression, .. } => evaluate(expression, target),
+        Expression::UnaryOperator { unop, expression } => {
+            unary(unop, evaluate(expression, target)?, target)
+        }
+        Expression::BinaryOperator { lhs, binop, rhs } => binary(binop, lhs, rhs, target),
+        _ => None,
+    }
+}
+
+fn unary(unop: &UnOp, value: Value, target: Target) -> Option<Value> {
+    match (unop, value) {
+        (UnOp::Not(_), value) => Some(Value::Boolean(!value.is_truthy())),
+        (UnOp::Minus(_), Value::Number(Number::Integer(integer))) => {
+            Some(Value::Number(Number::Integer(integer.wrapping_neg())))
+        }
+        (UnOp::Minus(_), Value::Number(Number::Float(float))) => {
+            float_result(-float).map(Value::Number)
+        }
+        (UnOp::Hash(_), Value::String(bytes)) => Some(Value::Number(if target.has_integers() {
+            Number::Integer(bytes.len() as i64)
+        } else {
+            Number::Float(bytes.len() as f64)
+        })),
+        _ => None,
+    }
+}
+
+fn binary(binop: &BinOp, lhs: &Expression, rhs: &Expression, target: Target) -> Option<Value> {
+    // The right operand is only evaluated when the left one doesn't decide the result
+    match binop {
+        BinOp::And(_) => {
+            let left = evaluate(lhs, target)?;
+            return if left.is_truthy() {
+                evaluate(rhs, target)
+            } else {
+                Some(left)
+            };
+        }
+        BinOp::Or(_) => {
+            let left = evaluate(lhs, target)?;
+            return if left.is_truthy() {
+                Some(left)
+            } else {
+                evaluate(rhs, target)
+            };
+        }
+        _ => {}
+    }
+    let (left, right) = (evaluate(lhs, target)?, evaluate(rhs, target)?);
+    match (binop, left, right) {
+        (BinOp::TwoEqual(_), left, right) => equals(&left, &right).map(Value::Boolean),
+        (BinOp::TildeEqual(_), left, right) => {
+            equals(&left, &right).map(|equal| Value::Boolean(!equal))
+        }
+        // Strings are compared with the C locale of the machine running the program
+        (BinOp::LessThan(_), Value::Number(left), Value::Number(right)) => Some(Value::Boolean(
+            left.to_exact_float()? < right.to_exact_float()?,
+        )),
+        (BinOp::LessThanEqual(_), Value::Number(left), Value::Number(right)) => Some(
+            Value::Boolean(left.to_exact_float()? <= right.to_exact_float()?),
+        ),
+        (BinOp::GreaterThan(_), Value::Number(left), Value::Number(right)) => Some(Value::Boolean(
+            left.to_exact_float()? > right.to_exact_float()?,
+        )),
+        (BinOp::GreaterThanEqual(_), Value::Number(left), Value::Number(right)) => Some(
+            Value::Boolean(left.to_exact_float()? >= right.to_exact_float()?),
+        ),
+        (BinOp::TwoDots(_), left, right) => {
+            let mut bytes = concat_operand(left, target)?;
+            bytes.extend(concat_operand(right, target)?);
+            Some(Value::String(bytes))
+        }
+        // Strings are coerced to numbers by arithmetic, which we leave to the program
+        (binop, Value::Number(left), Value::Number(right)) => {
+            arithmetic(binop, left, right, target).map(Value::Number)
+        }
+        _ => None,
+    }
+}
+
+fn equals(left: &Value, right: &Value) -> Option<bool> {
+    match (left, right) {
+        (Value::Number(Number::Integer(left)), Value::Number(Number::Integer(right))) => {
+            Some(left == right)
+        }
+        (Value::Number(left), Value::Number(right)) => {
+            Some(left.to_exact_float()? == right.to_exact_float()?)
+        }
+        (left, right) => Some(left == right),
+    }
+}
+
+fn concat_operand(value: Value, target: Target) -> Option<Vec<u8>> {
+    match value {
+        Value::String(bytes) => Some(bytes),
+        Value::Number(number) => number.to_lua_string(target).map(String::into_bytes),
+        _ => None,
+    }
+}
+
+/// Rejects results which have no literal: infinities, NaN and `-0`
+fn float_result(float: f64) -> Option<Number> {
+    (float.is_finite() && !(float == 0.0 && float.is_sign_negative()))
+        .then_some(Number::Float(float))
+}
+
+fn arithmetic(binop: &BinOp, left: Number, right: Number, target: Target) -> Option<Number> {
+    if let (Number::Integer(left), Number::Integer(right)) = (left, right) {
+        // Integer arithmetic wraps around, division by zero is an error
+        match binop {
+            BinOp::Plus(_) => return Some(Number::Integer(left.wrapping_add(right))),
+            BinOp::Minus(_) => return Some(Number::Integer(left.wrapping_sub(right))),
+            BinOp::Star(_) => return Some(Number::Integer(left.wrapping_mul(right))),
+            BinOp::DoubleSlash(_) if right != 0 => {
+                let quotient = left.wrapping_div(right);
+                let rounded_up = left.wrapping_rem(right) != 0 && (left ^ right) < 0;
+                return Some(Number::Integer(quotient - rounded_up as i64));
+            }
+            BinOp::Percent(_) if right != 0 => {
+                let remainder = left.wrapping_rem(right);
+                return Some(Number::Integer(
+                    if remainder != 0 && (remainder ^ right) < 0 {
+                        remainder + right
+                    } else {
+                        remainder
+                    },
+                ));
+            }
+            BinOp::DoubleSlash(_) | BinOp::Percent(_) => return None,
+            _ => {}
+        }
+    }
+    let (left, right) = (left.to_float(), right.to_float());
+    let result = match binop {
+        BinOp::Plus(_) => left + right,
+        BinOp::Minus(_) => left - right,
+        BinOp::Star(_) => left * right,
+        BinOp::Slash(_) => left / right,
+        BinOp::DoubleSlash(_) => (left / right).floor(),
+        BinOp::Percent(_) if target.has_integers() => {
+            let remainder = left % right;
+            if (remainder > 0.0 && right < 0.0)
+                || (remainder < 0.0 && right != remainder && right > 0.0)
+            {
+                remainder + right
+            } else {
+                remainder
+            }
+        }
+        BinOp::Percent(_) => left - (left / right).floor() * right,
+        BinOp::Caret(_) => power(left, right)?,
+        _ => return None,
+    };
+    float_result(result)
+}
+
+/// `pow` is only folded when every C library gives the exact result
+fn power(base: f64, exponent: f64) -> Option<f64> {
+    if base.fract() != 0.0 || exponent.fract() != 0.0 || !(0.0..=64.0).contains(&exponent) {
+        return None;
+    }
+    let result = base.powi(exponent as i32);
+    (result.abs() <= MAX_EXACT_INTEGER).then_some(result)
+}
+
+fn length(expression: &Expression) -> usize {
+    let renames = HashMap::new();
+    let mut emitter = Emitter::new(&renames);
+    emitter.expression(expression);
+    emitter.finish().len()
+}
+
+/// The value of an operator whose operands are all literals, when it is shorter than the
+/// operator
+pub fn fold(expression: &Expression, target: Target) -> Option<Expression> {
+    if !matches!(
+        expression,
+        Expression::BinaryOperator { .. } | Expression::UnaryOperator { .. }
+    ) {
+        return None;
+    }
+    let folded = evaluate(expression, target)?.to_expression(target)?;
+    if length(&folded) >= length(expression) {
+        return None;
+    }
+    trace!(
+        "Folded {} into {}",
+        expression.to_string().trim(),
+        folded.to_string().trim()
+    );
+    Some(folded)
+}
+
+#[cfg(test)]
+mod tests {
+    use full_moon::ast::LastStmt;
+
+    use super::*;
+
+    /// What `fold` turns the expression `source` into, as written by the emitter
+    fn folded(target: Target, source: &str) -> Option<String> {
+        let ast = full_moon::parse_fallible(&format!("return {source}"), target.lua_version())
+            .into_result()
+            .unwrap();
+        let Some(LastStmt::Return(x)) = ast.nodes().last_stmt() else {
+            unreachable!()
+        };
+        let renames = HashMap::new();
+        let mut emitter = Emitter::new(&renames);
+        emitter.expression(&fold(x.returns().iter().next().unwrap(), target)?);
+        Some(emitter.finish())
+    }
+
+    #[test]
+    fn folds_per_target() {
+        use Target::*;
+        let cases: &[(Target, &str, Option<&str>)] = &[
+            (Lua54, "1 + 2", Some("3")),
+            (Lua54, "1 + 2.0", Some("3.")),
+            (Lua54, "15 / 10", Some("1.5")),
+            (Lua54, "4 / 2", Some("2.")),
+            (Lua54, "7 // 2", Some("3")),
+            (Lua54, "-7 // 2", Some("-4")),
+            (Lua54, "7.0 // 2", Some("3.")),
+            (Lua53, "2 * 3", Some("6")),
+            (Lua53, "- -1", Some("1")),
+            (Lua51, "1 + 2.0", Some("3")),
+            (Lua51, "4 / 2", Some("2")),
+            (Lua51, "- -1", Some("1")),
+            (LuaJit, "10 - 4", Some("6")),
+            (Luau, "0.5 + 0.25", Some(".75")),
+            (Luau, "100000 * 100000", Some("1e10")),
+            (Luau, "not nil", Some("true")),
+            (Luau, "not 12", Some("false")),
+            (Luau, "'a' .. 'b'", Some("\"ab\"")),
+        ];
+        for &(target, source, expected) in cases {
+            assert_eq!(
+                folded(target, source).as_deref(),
+                expected,
+                "{source} on {target:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_what_isnt_exact_or_shorter() {
+        use Target::*;
+        let cases: &[(Target, &str)] = &[
+            // Infinity and NaN have no literal
+            (Lua54, "1/0"),
+            (Luau, "1/0"),
+            (Luau, "0/0"),
+            // Dividing an integer by zero is an error
+            (Lua54, "1//0"),
+            (Lua54, "1%0"),
+            // `pow` of the C library may round
+            (Luau, "2^53"),
+            (Lua54, "2^0.5"),
+            // Not shorter than the expression
+            (Lua54, "3/2"),
+            (Luau, "1/3"),
+            (Luau, "-1"),
+            // Strings are coerced by the program
+            (Luau, "'1' + 1"),
+        ];
+        for &(target, source) in cases {
+            assert_eq!(folded(target, source), None, "{source} on {target:?}");
+        }
+    }
+}
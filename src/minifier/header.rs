@@ -0,0 +1,52 @@
+use full_moon::{
+    ast::Ast,
+    node::Node,
+    tokenizer::{Token, TokenType},
+};
+
+/// Whether a comment before the code has to be kept: the shebang, Luau directives like
+/// `--!strict` and license comments like `--[[! ... ]]`
+fn is_kept(token: &Token) -> bool {
+    match token.token_type() {
+        TokenType::Shebang { .. } => true,
+        TokenType::SingleLineComment { comment } | TokenType::MultiLineComment { comment, .. } => {
+            comment.starts_with('!')
+        }
+        _ => false,
+    }
+}
+
+/// The comments before the code which have to be kept
+pub fn kept_comments(ast: &Ast) -> impl Iterator<Item = &Token> {
+    let first = ast.nodes().tokens().next().unwrap_or(ast.eof());
+    first.leading_trivia().filter(|token| is_kept(token))
+}
+
+/// Writes comments one per line, verbatim
+pub fn lines<'a>(comments: impl IntoIterator<Item = &'a Token>) -> String {
+    let mut lines = String::new();
+    for comment in comments {
+        lines.push_str(comment.to_string().trim_end());
+        lines.push('\n');
+    }
+    lines
+}
+
+/// The comments before the code which the minified code must start with
+pub fn header(ast: &Ast) -> String {
+    lines(kept_comments(ast))
+}
+
+/// Turns the text of the banner into `--` comments
+pub fn banner(text: &str) -> String {
+    let mut banner = String::new();
+    for line in text.lines() {
+        banner.push_str("--");
+        if !line.is_empty() {
+            banner.push(' ');
+            banner.push_str(line);
+        }
+        banner.push('\n');
+    }
+    banner
+}
@@ -0,0 +1,513 @@
+use full_moon::tokenizer::{StringLiteralQuoteType, Token, TokenType};
+
+use crate::Target;
+
+/// The value of a numeric literal, as the target represents it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Number {
+    /// An integer, only on targets with an integer subtype
+    Integer(i64),
+    Float(f64),
+}
+
+/// Parses the text of a numeric literal, `None` if it uses syntax we don't model or doesn't
+/// have a finite value
+pub fn parse_number(text: &str, target: Target) -> Option<Number> {
+    let text = if target == Target::Luau {
+        text.replace('_', "")
+    } else {
+        text.to_string()
+    };
+    let lower = text.to_ascii_lowercase();
+    let (digits, radix) = if let Some(digits) = lower.strip_prefix("0x") {
+        (digits, 16)
+    } else if let Some(digits) = lower.strip_prefix("0b").filter(|_| target == Target::Luau) {
+        (digits, 2)
+    } else {
+        let value: f64 = lower.parse().ok().filter(|value: &f64| value.is_finite())?;
+        if target.has_integers() && !lower.contains(['.', 'e']) {
+            // Decimal integers which don't fit are read as floats
+            return Some(match lower.parse::<i64>() {
+                Ok(integer) => Number::Integer(integer),
+                Err(_) => Number::Float(value),
+            });
+        }
+        return Some(Number::Float(value));
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        // Hexadecimal floats and LuaJIT's integer suffixes
+        return None;
+    }
+    if target.has_integers() {
+        // Hexadecimal integers wrap around instead of becoming floats
+        let value = digits.chars().fold(0u64, |value, c| {
+            value
+                .wrapping_mul(radix as u64)
+                .wrapping_add(c.to_digit(radix).unwrap() as u64)
+        });
+        return Some(Number::Integer(value as i64));
+    }
+    let value = u64::from_str_radix(digits, radix).ok()?;
+    // Larger values depend on how the target rounds them
+    (value <= 1 << 53).then_some(Number::Float(value as f64))
+}
+
+impl Number {
+    /// Whether two numbers are the same value of the same subtype, bit for bit
+    fn same(self, other: Number) -> bool {
+        match (self, other) {
+            (Number::Integer(a), Number::Integer(b)) => a == b,
+            (Number::Float(a), Number::Float(b)) => a.to_bits() == b.to_bits(),
+            _ => false,
+        }
+    }
+}
+
+/// Ways to write a float, from the shortest digits which read back as the same value
+fn float_candidates(float: f64) -> Vec<String> {
+    // `{:e}` gives the shortest digits which round trip, as `d.ddde±x`
+    let scientific = format!("{float:e}");
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    let digits = mantissa.replace('.', "");
+    let length = digits.len() as i32;
+    let mut candidates = vec![
+        scientific.clone(),
+        // `1.5e-7` as `15e-8`
+        format!("{digits}e{}", exponent - (length - 1)),
+    ];
+    if exponent >= length - 1 {
+        let zeros = "0".repeat((exponent - (length - 1)) as usize);
+        candidates.push(format!("{digits}{zeros}"));
+        candidates.push(format!("{digits}{zeros}."));
+    } else if exponent < 0 {
+        candidates.push(format!(".{}{digits}", "0".repeat((-exponent - 1) as usize)));
+    } else {
+        let (whole, fraction) = digits.split_at(exponent as usize + 1);
+        candidates.push(format!("{whole}.{fraction}"));
+    }
+    if float.fract() == 0.0 && float <= (1u64 << 53) as f64 {
+        candidates.push(format!("0x{:x}", float as u64));
+    }
+    candidates
+}
+
+/// Formats a number as the shortest literal which reads back as the same value. `None` if
+/// there is no such literal, like for negative numbers other than integers which a
+/// hexadecimal literal wraps around to.
+pub fn format_number(number: Number, target: Target) -> Option<String> {
+    let candidates = match number {
+        Number::Integer(integer) if integer >= 0 => {
+            vec![integer.to_string(), format!("0x{integer:x}")]
+        }
+        // Only a hexadecimal literal wraps around to a negative integer
+        Number::Integer(integer) => vec![format!("0x{:x}", integer as u64)],
+        Number::Float(float) if float.is_finite() && float.is_sign_positive() => {
+            float_candidates(float)
+        }
+        Number::Float(_) => return None,
+    };
+    candidates
+        .into_iter()
+        .filter(|candidate| {
+            parse_number(candidate, target).is_some_and(|parsed| parsed.same(number))
+        })
+        .min_by_key(String::len)
+}
+
+/// The shortest literal for the value of a numeric literal, `None` if the literal is already
+/// the shortest or uses syntax we don't model
+pub fn shorten_number(text: &str, target: Target) -> Option<String> {
+    let shortest = format_number(parse_number(text, target)?, target)?;
+    (shortest.len() < text.len()).then_some(shortest)
+}
+
+/// Whether the target reads `\u{...}` escapes. LuaJIT 2.0 doesn't, so it only gets `\ddd`.
+fn has_unicode_escapes(target: Target) -> bool {
+    matches!(target, Target::Lua53 | Target::Lua54 | Target::Luau)
+}
+
+/// Decodes the bytes of a string literal, `None` if it uses an escape the target doesn't have
+/// or is a long string with carriage returns, whose line breaks the versions read differently
+pub fn decode_string(token: &Token, target: Target) -> Option<Vec<u8>> {
+    let TokenType::StringLiteral {
+        literal,
+        quote_type,
+        ..
+    } = token.token_type()
+    else {
+        return None;
+    };
+    if *quote_type == StringLiteralQuoteType::Brackets {
+        if literal.contains('\r') {
+            return None;
+        }
+        // A newline right after the opening bracket isn't part of the string
+        let literal = literal.as_str();
+        let literal = literal.strip_prefix('\n').unwrap_or(literal);
+        return Some(literal.as_bytes().to_vec());
+    }
+    let modern = target != Target::Lua51;
+    let unicode = has_unicode_escapes(target);
+    let mut bytes = Vec::new();
+    let mut chars = literal.as_str().chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+        match chars.next()? {
+            'a' => bytes.push(0x07),
+            'b' => bytes.push(0x08),
+            'f' => bytes.push(0x0c),
+            'n' => bytes.push(b'\n'),
+            'r' => bytes.push(b'\r'),
+            't' => bytes.push(b'\t'),
+            'v' => bytes.push(0x0b),
+            c @ ('\\' | '"' | '\'') => bytes.push(c as u8),
+            c @ ('\n' | '\r') => {
+                // `\r\n` and `\n\r` are a single line break
+                chars.next_if(|&next| matches!(next, '\n' | '\r') && next != c);
+                bytes.push(b'\n');
+            }
+            'x' if modern => {
+                let high = chars.next()?.to_digit(16)?;
+                let low = chars.next()?.to_digit(16)?;
+                bytes.push((high * 16 + low) as u8);
+            }
+            'z' if modern => {
+                while chars
+                    .next_if(|&c| c.is_ascii_whitespace() || c == '\x0b')
+                    .is_some()
+                {}
+            }
+            'u' if unicode => {
+                if chars.next()? != '{' {
+                    return None;
+                }
+                let mut code = 0u32;
+                loop {
+                    match chars.next()? {
+                        '}' => break,
+                        c => code = code.checked_mul(16)?.checked_add(c.to_digit(16)?)?,
+                    }
+                }
+                let mut buffer = [0; 4];
+                bytes.extend_from_slice(char::from_u32(code)?.encode_utf8(&mut buffer).as_bytes());
+            }
+            c @ '0'..='9' => {
+                let mut code = c.to_digit(10).unwrap();
+                for _ in 0..2 {
+                    match chars.next_if(char::is_ascii_digit) {
+                        Some(c) => code = code * 10 + c.to_digit(10).unwrap(),
+                        None => break,
+                    }
+                }
+                bytes.push(u8::try_from(code).ok()?);
+            }
+            _ => return None,
+        }
+    }
+    Some(bytes)
+}
+
+/// Escapes a byte which has no shorter escape as `\ddd`
+fn push_decimal_escape(literal: &mut String, byte: u8, next: Option<&u8>) {
+    // A digit after the escape would be read as part of it
+    if next.is_some_and(u8::is_ascii_digit) {
+        literal.push_str(&format!("\\{byte:03}"));
+    } else {
+        literal.push_str(&format!("\\{byte}"));
+    }
+}
+
+/// The UTF-8 character starting at `bytes[0]`, if there is a valid one
+fn utf8_char(bytes: &[u8]) -> Option<char> {
+    (2..=4.min(bytes.len()))
+        .find_map(|length| std::str::from_utf8(&bytes[..length]).ok())
+        .and_then(|text| text.chars().next())
+}
+
+/// Writes bytes between `quote`s, escaping what has to be
+fn quoted(bytes: &[u8], quote: u8, target: Target, ascii_only: bool) -> String {
+    let unicode_escapes = has_unicode_escapes(target);
+    let mut literal = String::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = bytes[i];
+        match byte {
+            b'\\' => literal.push_str("\\\\"),
+            _ if byte == quote => {
+                literal.push('\\');
+                literal.push(quote as char);
+            }
+            b'\n' => literal.push_str("\\n"),
+            b'\r' => literal.push_str("\\r"),
+            b'\t' => literal.push_str("\\t"),
+            0x07 => literal.push_str("\\a"),
+            0x08 => literal.push_str("\\b"),
+            0x0b => literal.push_str("\\v"),
+            0x0c => literal.push_str("\\f"),
+            0x20..=0x7e => literal.push(byte as char),
+            0x80.. => match utf8_char(&bytes[i..]) {
+                Some(c) => {
+                    let length = c.len_utf8();
+                    let mut escaped = String::new();
+                    for j in i..i + length {
+                        push_decimal_escape(&mut escaped, bytes[j], bytes.get(j + 1));
+                    }
+                    if !ascii_only {
+                        literal.push(c);
+                    } else if unicode_escapes
+                        && format!("\\u{{{:x}}}", c as u32).len() < escaped.len()
+                    {
+                        literal.push_str(&format!("\\u{{{:x}}}", c as u32));
+                    } else {
+                        literal.push_str(&escaped);
+                    }
+                    i += length;
+                    continue;
+                }
+                None => push_decimal_escape(&mut literal, byte, bytes.get(i + 1)),
+            },
+            _ => push_decimal_escape(&mut literal, byte, bytes.get(i + 1)),
+        }
+        i += 1;
+    }
+    literal
+}
+
+/// Writes bytes between long brackets, `None` if they would read back differently: long
+/// brackets turn every line break into `\n` and can't hold escapes
+fn long_brackets(bytes: &[u8], target: Target, ascii_only: bool) -> Option<(String, usize)> {
+    let content = std::str::from_utf8(bytes).ok()?;
+    if content
+        .chars()
+        .any(|c| (c.is_control() && c != '\n' && c != '\t') || (ascii_only && !c.is_ascii()))
+    {
+        return None;
+    }
+    // A line break right after the opening bracket is skipped
+    let prefix = if content.starts_with('\n') { "\n" } else { "" };
+    // Lua 5.1 rejects `[[` inside `[[...]]`
+    let lowest = usize::from(target == Target::Lua51 && content.contains("[["));
+    (lowest..)
+        .map(|depth| {
+            let close = format!("]{}]", "=".repeat(depth));
+            (depth, format!("{content}{close}").find(&close))
+        })
+        .find(|(_, end)| *end == Some(content.len()))
+        .map(|(depth, _)| (format!("{prefix}{content}"), depth))
+}
+
+/// Writes bytes as the shortest string literal which reads back as the same bytes
+pub fn string_literal(bytes: &[u8], target: Target, ascii_only: bool) -> TokenType {
+    let mut candidates = vec![
+        (
+            quoted(bytes, b'"', target, ascii_only),
+            0,
+            StringLiteralQuoteType::Double,
+        ),
+        (
+            quoted(bytes, b'\'', target, ascii_only),
+            0,
+            StringLiteralQuoteType::Single,
+        ),
+    ];
+    if let Some((literal, depth)) = long_brackets(bytes, target, ascii_only) {
+        candidates.push((literal, depth, StringLiteralQuoteType::Brackets));
+    }
+    let (literal, multi_line_depth, quote_type) = candidates
+        .into_iter()
+        .min_by_key(|(literal, depth, _)| literal.len() + 2 * depth)
+        .unwrap();
+    TokenType::StringLiteral {
+        literal: literal.into(),
+        multi_line_depth,
+        quote_type,
+    }
+}
+
+/// The shortest literal for the bytes of a string literal, `None` if the literal is already
+/// the shortest or uses an escape the target doesn't have. With `ascii_only`, a literal with
+/// other characters is always rewritten.
+pub fn shorten_string(token: &Token, target: Target, ascii_only: bool) -> Option<TokenType> {
+    let bytes = decode_string(token, target)?;
+    let shortest = string_literal(&bytes, target, ascii_only);
+    let (original, shortest_text) = (token.to_string(), Token::new(shortest.clone()).to_string());
+    (shortest_text.len() < original.len() || (ascii_only && !original.is_ascii()))
+        .then_some(shortest)
+}
+
+/// Escapes the characters outside of ASCII in a quoted or interpolated string literal as
+/// `\u{...}` where the target reads them, and byte by byte as `\ddd` elsewhere
+pub fn escape_non_ascii(literal: &str, target: Target) -> String {
+    let unicode = has_unicode_escapes(target);
+    let mut escaped = String::new();
+    let mut chars = literal.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii() {
+            escaped.push(c);
+        } else if unicode {
+            escaped.push_str(&format!("\\u{{{:x}}}", c as u32));
+        } else {
+            let mut buffer = [0; 4];
+            let bytes = c.encode_utf8(&mut buffer).as_bytes();
+            let after = chars.peek().filter(|c| c.is_ascii()).map(|&c| c as u8);
+            for (i, byte) in bytes.iter().enumerate() {
+                push_decimal_escape(&mut escaped, *byte, bytes.get(i + 1).or(after.as_ref()));
+            }
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What `shorten_string` rewrites the string literal `source` to
+    fn shortened(target: Target, source: &str, ascii_only: bool) -> Option<String> {
+        let tokens = full_moon::tokenizer::Lexer::new(source, target.lua_version())
+            .collect()
+            .unwrap();
+        let token = tokens
+            .into_iter()
+            .find(|token| matches!(token.token_type(), TokenType::StringLiteral { .. }))?;
+        Some(Token::new(shorten_string(&token, target, ascii_only)?).to_string())
+    }
+
+    #[test]
+    fn shortens_strings() {
+        use Target::*;
+        let newlines = format!("\"{}\"", "\\n".repeat(8));
+        let long = format!("[[\n{}]]", "\n".repeat(8));
+        let nested = format!("\"[[{}\"", "\\n".repeat(8));
+        let closing = format!("\"]]{}\"", "\\n".repeat(8));
+        let cases: &[(Target, &str, Option<&str>)] = &[
+            // The quote which needs fewer escapes
+            (Luau, r#"'it\'s'"#, Some(r#""it's""#)),
+            (Luau, r#""say \"hi\"""#, Some(r#"'say "hi"'"#)),
+            (Lua51, r#""\65\66""#, Some(r#""AB""#)),
+            (Lua54, r#""\x41\z   B""#, Some(r#""AB""#)),
+            (Luau, r#""A""#, None),
+            // Long brackets, which skip a line break right after the opening bracket
+            (Lua51, &newlines, Some(&long)),
+            (Luau, "[[\nabc]]", Some(r#""abc""#)),
+            (Lua54, &closing, Some("[=[]]\n\n\n\n\n\n\n\n]=]")),
+            // Lua 5.1 rejects `[[` inside `[[...]]`
+            (Lua51, &nested, Some("[=[[[\n\n\n\n\n\n\n\n]=]")),
+            (Lua54, &nested, Some("[[[[\n\n\n\n\n\n\n\n]]")),
+            // Versions read the line breaks of long strings differently
+            (Lua51, "[=[]]\r\n]=]", None),
+            (Luau, "[=[]]\r]=]", None),
+            // Escapes the target doesn't have
+            (Lua51, r#""\x41""#, None),
+        ];
+        for &(target, source, expected) in cases {
+            assert_eq!(
+                shortened(target, source, false).as_deref(),
+                expected,
+                "{source} on {target:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn escapes_non_ascii_per_target() {
+        use Target::*;
+        let cases: &[(Target, &str, Option<&str>)] = &[
+            (Lua51, "\"héllo\"", Some(r#""h\195\169llo""#)),
+            (LuaJit, "\"héllo\"", Some(r#""h\195\169llo""#)),
+            (Lua54, "\"héllo\"", Some(r#""h\u{e9}llo""#)),
+            (Luau, "\"héllo\"", Some(r#""h\u{e9}llo""#)),
+            // A digit after an escape would be read as part of it
+            (LuaJit, "\"é1\"", Some(r#""\195\1691""#)),
+            (LuaJit, r#""h\195\169llo""#, None),
+            (Lua54, r#""h\195\169llo""#, Some(r#""h\u{e9}llo""#)),
+            (Luau, "[[é]]", Some(r#""\u{e9}""#)),
+        ];
+        for &(target, source, expected) in cases {
+            assert_eq!(
+                shortened(target, source, true).as_deref(),
+                expected,
+                "{source} on {target:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn shortens_numbers() {
+        use Target::*;
+        let cases: &[(Target, &str, Option<&str>)] = &[
+            (Luau, "8640000", Some("864e4")),
+            (Luau, "1000000", Some("1e6")),
+            (Luau, "0.5", Some(".5")),
+            (Luau, "0xff", Some("255")),
+            (Luau, "0x10000000", Some("268435456")),
+            (Luau, "1_000", Some("1e3")),
+            (Luau, "0b1010", Some("10")),
+            (Lua51, "1.50", Some("1.5")),
+            (Lua51, "3.0", Some("3")),
+            (Lua54, "3.0", Some("3.")),
+            (Lua54, "100.0", Some("1e2")),
+            (Lua54, "100000000000000000000", Some("1e20")),
+        ];
+        for &(target, text, expected) in cases {
+            assert_eq!(
+                shorten_number(text, target).as_deref(),
+                expected,
+                "{text} on {target:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_numbers_which_would_change() {
+        use Target::*;
+        let cases: &[(Target, &str)] = &[
+            // Already the shortest
+            (Luau, "86400"),
+            (Luau, "1e6"),
+            // `1e6` would be a float
+            (Lua54, "1000000"),
+            (Lua53, "9007199254740993"),
+            // Only a hexadecimal literal wraps around to a negative integer
+            (Lua54, "0xffffffffffffffff"),
+            // Hexadecimal floats and LuaJIT's integer suffixes aren't modelled
+            (Lua52, "0x1p4"),
+            (LuaJit, "1000000LL"),
+        ];
+        for &(target, text) in cases {
+            assert_eq!(shorten_number(text, target), None, "{text} on {target:?}");
+        }
+    }
+
+    #[test]
+    fn formatted_numbers_read_back() {
+        let numbers = [
+            Number::Float(0.1),
+            Number::Float(1e300),
+            Number::Float(123456.789),
+            Number::Float(2f64.powi(60)),
+            Number::Float(5e-324),
+            Number::Integer(i64::MAX),
+            Number::Integer(i64::MIN),
+            Number::Integer(864000),
+        ];
+        for target in [Target::Lua51, Target::Lua54, Target::LuaJit, Target::Luau] {
+            for number in numbers {
+                let number = match (number, target.has_integers()) {
+                    (Number::Integer(integer), false) => Number::Float(integer as f64),
+                    _ => number,
+                };
+                let Some(text) = format_number(number, target) else {
+                    continue;
+                };
+                let parsed = parse_number(&text, target).unwrap();
+                assert!(parsed.same(number), "{text} on {target:?}");
+            }
+        }
+    }
+}
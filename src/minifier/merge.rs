@@ -0,0 +1,392 @@
+use std::collections::HashSet;
+
+use full_moon::{
+    self,
+    ast::{
+        luau::TypeSpecifier,
+        punctuated::{Pair, Punctuated},
+        Assignment, BinOp, Expression, Field, LocalAssignment, Stmt, UnOp, Var,
+    },
+    node::Node,
+    tokenizer::{TokenReference, TokenType},
+};
+
+use crate::minifier::{constants::*, Minifier};
+
+/// Adjacent assignments of the same kind, merged into a single statement.
+///
+/// Statements are only merged when the result can't behave differently: expressions are
+/// still evaluated left to right and no expression reads a variable which the statements
+/// merged before it write to.
+pub enum Merge {
+    Local(Box<LocalMerge>),
+    Assignment(AssignmentMerge),
+}
+
+pub struct LocalMerge {
+    /// The `local` keyword of the first statement
+    local_token: TokenReference,
+    names: Vec<TokenReference>,
+    types: Vec<Option<TypeSpecifier>>,
+    expressions: Vec<Expression>,
+    /// Bindings declared by the merged statement
+    bindings: HashSet<usize>,
+    /// Names of the declared bindings after renaming
+    final_names: HashSet<String>,
+}
+
+pub struct AssignmentMerge {
+    variables: Vec<Var>,
+    expressions: Vec<Expression>,
+    /// Bindings written by the merged statement, `None` if it writes to anything but locals
+    targets: Option<HashSet<usize>>,
+}
+
+impl Merge {
+    /// Starts a merge from `stmt` if it is an assignment
+    pub fn new(minifier: &Minifier, stmt: &Stmt) -> Option<Self> {
+        match stmt {
+            // A statement can have only one `<close>` variable, so attributes are left alone
+            Stmt::LocalAssignment(x) if !has_attributes(x) => {
+                let mut merge = LocalMerge {
+                    local_token: x.local_token().clone(),
+                    names: Vec::new(),
+                    types: Vec::new(),
+                    expressions: Vec::new(),
+                    bindings: HashSet::new(),
+                    final_names: HashSet::new(),
+                };
+                merge.append(minifier, x);
+                Some(Merge::Local(Box::new(merge)))
+            }
+            Stmt::Assignment(x) => {
+                let targets = x
+                    .variables()
+                    .iter()
+                    .map(|var| local_target(minifier, var))
+                    .collect();
+                Some(Merge::Assignment(AssignmentMerge {
+                    variables: x.variables().iter().cloned().collect(),
+                    expressions: x.expressions().iter().cloned().collect(),
+                    targets,
+                }))
+            }
+            _ => None,
+        }
+    }
+
+    /// Merges `stmt` into this statement, returns false if that isn't safe
+    pub fn push(&mut self, minifier: &Minifier, stmt: &Stmt) -> bool {
+        match (self, stmt) {
+            (Merge::Local(merge), Stmt::LocalAssignment(x)) if !has_attributes(x) => {
+                merge.push(minifier, x)
+            }
+            (Merge::Assignment(merge), Stmt::Assignment(x)) => merge.push(minifier, x),
+            _ => false,
+        }
+    }
+
+    /// Builds the merged statement
+    pub fn into_stmt(self) -> Stmt {
+        match self {
+            Merge::Local(merge) => {
+                let mut local_assignment = LocalAssignment::new(punctuated(merge.names))
+                    .with_local_token(merge.local_token)
+                    .with_type_specifiers(merge.types);
+                if !merge.expressions.is_empty() {
+                    local_assignment = local_assignment
+                        .with_equal_token(EQ_TOKEN.clone())
+                        .with_expressions(punctuated(merge.expressions));
+                }
+                Stmt::LocalAssignment(local_assignment)
+            }
+            Merge::Assignment(merge) => Stmt::Assignment(
+                Assignment::new(punctuated(merge.variables), punctuated(merge.expressions))
+                    .with_equal_token(EQ_TOKEN.as_ref().unwrap().clone()),
+            ),
+        }
+    }
+}
+
+impl LocalMerge {
+    fn push(&mut self, minifier: &Minifier, x: &LocalAssignment) -> bool {
+        // Two bindings with the same name in one statement leave it unclear which one wins
+        if x.names()
+            .iter()
+            .any(|name| self.final_names.contains(&final_name(minifier, name)))
+        {
+            return false;
+        }
+        // The values would no longer see the bindings declared before them
+        if x.expressions()
+            .iter()
+            .any(|exp| reads_any(minifier, exp, &self.bindings))
+        {
+            return false;
+        }
+        // `local a, b` followed by `local c` needs no values at all
+        let needs_values = !x.expressions().is_empty() || !self.expressions.is_empty();
+        if needs_values
+            && !pad_before(
+                &mut self.expressions,
+                self.names.len(),
+                x.expressions(),
+                x.names().len(),
+            )
+        {
+            return false;
+        }
+        self.append(minifier, x);
+        true
+    }
+
+    fn append(&mut self, minifier: &Minifier, x: &LocalAssignment) {
+        for name in x.names() {
+            if let Some(&binding) = minifier
+                .bindings
+                .get(&name.token().start_position().bytes())
+            {
+                self.bindings.insert(binding);
+            }
+            self.final_names.insert(final_name(minifier, name));
+            self.names.push(name.clone());
+        }
+        for var_type in x.type_specifiers() {
+            self.types.push(var_type.cloned());
+        }
+        for exp in x.expressions() {
+            self.expressions.push(exp.clone());
+        }
+    }
+}
+
+impl AssignmentMerge {
+    fn push(&mut self, minifier: &Minifier, x: &Assignment) -> bool {
+        // Writing to anything but a local may run a metamethod, which would now run later
+        let Some(targets) = &self.targets else {
+            return false;
+        };
+        let mut new_targets = HashSet::new();
+        for var in x.variables() {
+            match local_target(minifier, var) {
+                Some(binding) if !targets.contains(&binding) => {
+                    new_targets.insert(binding);
+                }
+                _ => return false,
+            }
+        }
+        // All values are evaluated before anything is assigned, so the new values must
+        // neither observe nor cause side effects and can't read the variables written earlier
+        if !x
+            .expressions()
+            .iter()
+            .all(|exp| is_pure(minifier, exp) && !reads_any(minifier, exp, targets))
+        {
+            return false;
+        }
+        if !pad_before(
+            &mut self.expressions,
+            self.variables.len(),
+            x.expressions(),
+            x.variables().len(),
+        ) {
+            return false;
+        }
+        self.targets = Some(targets.union(&new_targets).copied().collect());
+        self.variables.extend(x.variables().iter().cloned());
+        self.expressions.extend(x.expressions().iter().cloned());
+        true
+    }
+}
+
+/// Whether a local assignment declares a `<const>` or `<close>` variable
+pub fn has_attributes(x: &LocalAssignment) -> bool {
+    x.attributes().any(|attribute| attribute.is_some())
+}
+
+/// Separates `items` with commas
+pub fn punctuated<T>(items: Vec<T>) -> Punctuated<T> {
+    let count = items.len();
+    let mut punctuated = Punctuated::new();
+    for (i, item) in items.into_iter().enumerate() {
+        if i + 1 == count {
+            punctuated.push(Pair::End(item));
+        } else {
+            punctuated.push(Pair::Punctuated(item, COMMA.clone()));
+        }
+    }
+    punctuated
+}
+
+/// Pads `expressions`, the values of `count` names, with `nil` so that `added`, the values of
+/// `added_count` more names, can follow them. Returns false if they can't be followed by more
+/// values without changing how many values are assigned.
+fn pad_before(
+    expressions: &mut Vec<Expression>,
+    count: usize,
+    added: &Punctuated<Expression>,
+    added_count: usize,
+) -> bool {
+    if expressions.len() > count {
+        return false;
+    }
+    if expressions.len() < count {
+        if expressions.last().is_some_and(is_multiple_values) {
+            return false;
+        }
+        expressions.resize(count, NIL_SYMBOL.clone());
+    }
+    // A call or `...` left last would spill its other values into the new names
+    if added.is_empty() && expressions.last().is_some_and(is_multiple_values) {
+        expressions.resize(count + added_count, NIL_SYMBOL.clone());
+    }
+    true
+}
+
+/// Whether an expression can evaluate to more than one value at the end of a list
+pub fn is_multiple_values(exp: &Expression) -> bool {
+    match exp {
+        Expression::FunctionCall(_) => true,
+        Expression::Symbol(symbol) => symbol.token().to_string() == "...",
+        _ => false,
+    }
+}
+
+/// The local binding a target writes to, if it is a plain local variable
+fn local_target(minifier: &Minifier, var: &Var) -> Option<usize> {
+    match var {
+        Var::Name(name) => minifier
+            .bindings
+            .get(&name.token().start_position().bytes())
+            .copied(),
+        _ => None,
+    }
+}
+
+/// The name an identifier has after renaming
+fn final_name(minifier: &Minifier, name: &TokenReference) -> String {
+    match minifier.renames.get(&name.token().start_position().bytes()) {
+        Some(renamed) => renamed.clone(),
+        None => name.token().to_string(),
+    }
+}
+
+/// Whether an expression, including any function inside it, references one of `bindings`
+fn reads_any(minifier: &Minifier, exp: &Expression, bindings: &HashSet<usize>) -> bool {
+    exp.tokens().any(|token| {
+        matches!(token.token_type(), TokenType::Identifier { .. })
+            && minifier
+                .bindings
+                .get(&token.token().start_position().bytes())
+                .is_some_and(|binding| bindings.contains(binding))
+    })
+}
+
+/// Whether evaluating an expression can't run any code, so it can be moved past assignments
+fn is_pure(minifier: &Minifier, exp: &Expression) -> bool {
+    match exp {
+        Expression::Number(_) | Expression::String(_) | Expression::Symbol(_) => true,
+        // Creating a closure doesn't run its body
+        Expression::Function(_) => true,
+        Expression::TableConstructor(table) => table.fields().iter().all(|field| match field {
+            Field::ExpressionKey { key, value, .. } => {
+                is_pure(minifier, key) && is_pure(minifier, value)
+            }
+            Field::NameKey { value, .. } => is_pure(minifier, value),
+            Field::NoKey(value) => is_pure(minifier, value),
+            _ => false,
+        }),
+        Expression::Parentheses { expression, .. } => is_pure(minifier, expression),
+        Expression::UnaryOperator {
+            unop: UnOp::Not(_),
+            expression,
+        } => is_pure(minifier, expression),
+        Expression::BinaryOperator {
+            lhs,
+            binop: BinOp::And(_) | BinOp::Or(_),
+            rhs,
+        } => is_pure(minifier, lhs) && is_pure(minifier, rhs),
+        Expression::TypeAssertion { expression, .. } => is_pure(minifier, expression),
+        // Reading a global may run an `__index` metamethod
+        Expression::Var(Var::Name(name)) => minifier
+            .bindings
+            .contains_key(&name.token().start_position().bytes()),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{minify, Options, Target};
+
+    /// Minifies `source` with merging, but without renaming, and checks the result
+    fn merged_for(target: Target, source: &str) -> String {
+        let options = Options {
+            rename_locals: false,
+            verify: true,
+            banner: None,
+            dialect: target,
+            target,
+            ..Options::default()
+        };
+        minify(source, &options).unwrap().code
+    }
+
+    fn merged(source: &str) -> String {
+        merged_for(Target::Luau, source)
+    }
+
+    #[test]
+    fn merges_independent_statements() {
+        assert_eq!(merged("local a = 1 local b = 2"), "local a,b=1,2");
+        assert_eq!(merged("local a local b"), "local a,b");
+        assert_eq!(merged("local a, b a = 1 b = 2"), "local a,b a,b=1,2");
+    }
+
+    #[test]
+    fn keeps_impure_values_apart() {
+        assert_eq!(merged("local a, b a = 1 b = f()"), "local a,b a=1 b=f()");
+        assert_eq!(merged("local a, b a = 1 b = g.x"), "local a,b a=1 b=g.x");
+    }
+
+    #[test]
+    fn keeps_values_reading_earlier_names_apart() {
+        assert_eq!(merged("local a = 1 local b = a"), "local a=1 local b=a");
+        assert_eq!(merged("local a, b a = 1 b = a"), "local a,b a=1 b=a");
+        assert_eq!(
+            merged("local a = 1 local b = function() return a end"),
+            "local a=1 local b=function()return a end"
+        );
+    }
+
+    #[test]
+    fn keeps_spread_values_last() {
+        assert_eq!(
+            merged("local a, b = f() local c = 1"),
+            "local a,b=f()local c=1"
+        );
+        assert_eq!(
+            merged("local a = 1, 2 local b = 3"),
+            "local a=1,2 local b=3"
+        );
+    }
+
+    #[test]
+    fn pads_after_multiple_values() {
+        assert_eq!(merged("local a = f() local b"), "local a,b=f(),nil");
+        assert_eq!(
+            merged("local function g(...) local a = ... local b return a, b end"),
+            "local function g(...)local a,b=...,nil return a,b end"
+        );
+        assert_eq!(merged("local a = f() local b = 1"), "local a,b=f(),1");
+    }
+
+    #[test]
+    fn leaves_globals_and_attributes_alone() {
+        assert_eq!(merged("x = 1 y = 2"), "x=1 y=2");
+        assert_eq!(
+            merged_for(Target::Lua54, "local a <const> = 1 local b = 2"),
+            "local a<const> =1 local b=2"
+        );
+    }
+}
@@ -2,327 +2,383 @@ use std::collections::HashMap;
 
 use full_moon::{
     self,
-    ast::{
-        luau::TypeSpecifier,
-        punctuated::{Pair, Punctuated},
-        Assignment, Block, Expression, Field, FunctionArgs, LocalAssignment, Parameter, Stmt,
-        Suffix, Var,
-    },
+    ast::{Block, ElseIf, Expression, FunctionArgs, FunctionBody, Stmt},
     tokenizer::{Token, TokenReference, TokenType},
+    visitors::{VisitMut, VisitorMut},
 };
+use log::{debug, trace};
 
-use crate::minifier::constants::*;
+use crate::{
+    error::ParseError,
+    minifier::{
+        emitter::Emitter,
+        fold::fold,
+        literals::{shorten_number, shorten_string},
+        merge::Merge,
+        scope::ScopeTree,
+        types::TypeStripper,
+    },
+    Error, Options, Output, Rename, RenameKind, SourceMap, Stats, TypeMode,
+};
 
 mod constants;
-mod punctuator;
+mod dead_code;
+pub(crate) mod emitter;
+mod fold;
+pub(crate) mod header;
+pub(crate) mod literals;
+mod merge;
+mod reference;
+pub(crate) mod scope;
+mod target;
+mod types;
 mod utils;
-mod whitespace;
+mod verify;
 
-pub(crate) struct Minifier {
-    code: String,
-    global_names: HashMap<String, String>,
+/// Folds the expressions it visits and minifies the bodies of the outermost anonymous
+/// functions, which minify the expressions and functions nested in them in turn
+struct Expressions<'a, 'm> {
+    minifier: &'a mut Minifier<'m>,
+    /// How many function bodies the visitor is in
+    depth: usize,
 }
 
-impl Minifier {
-    pub fn new(code: &str) -> Self {
-        Minifier {
-            code: String::from(code),
-            global_names: HashMap::new(),
+impl VisitorMut for Expressions<'_, '_> {
+    fn visit_function_body(&mut self, body: FunctionBody) -> FunctionBody {
+        self.depth += 1;
+        body
+    }
+
+    fn visit_function_body_end(&mut self, body: FunctionBody) -> FunctionBody {
+        self.depth -= 1;
+        if self.depth == 0 {
+            self.minifier.minify_function_body(&body)
+        } else {
+            body
         }
     }
 
-    fn minify_function_args(&self, function_args: &FunctionArgs) -> FunctionArgs {
-        match function_args {
-            FunctionArgs::Parentheses {
-                parentheses,
-                arguments,
-            } => {
-                let mut new_args: Punctuated<Expression> = Punctuated::new();
-                for arg in arguments {
-                    let new_arg = whitespace::trim_exp(arg);
-                    new_args.push(Pair::new(new_arg, None))
-                }
-                new_args = punctuator::punctuate_name(new_args, &COMMA);
-                FunctionArgs::Parentheses {
-                    parentheses: whitespace::trim_cspan(parentheses),
-                    arguments: new_args,
+    fn visit_expression_end(&mut self, expression: Expression) -> Expression {
+        if self.depth > 0 {
+            return expression;
+        }
+        let options = self.minifier.options;
+        let expression = match options.fold_constants {
+            true => match fold(&expression, options.target) {
+                Some(folded) => {
+                    self.minifier.folded_constants += 1;
+                    folded
                 }
-            }
-            FunctionArgs::TableConstructor(x) => {
-                let mut new_fields: Punctuated<Field> = Punctuated::new();
-                for field in x.fields() {
-                    let new_field: Field;
-                    match field {
-                        Field::ExpressionKey {
-                            brackets,
-                            key,
-                            equal,
-                            value,
-                        } => {
-                            let new_key = whitespace::trim_exp(key);
-                            let new_value = whitespace::trim_exp(value);
-                            new_field = Field::ExpressionKey {
-                                brackets: whitespace::trim_cspan(brackets),
-                                key: new_key,
-                                equal: equal.clone(),
-                                value: new_value,
-                            }
-                        }
-                        Field::NameKey { key, equal, value } => {
-                            let new_key = whitespace::trim(key);
-                            let new_value = whitespace::trim_exp(value);
-                            new_field = Field::NameKey {
-                                key: new_key,
-                                equal: equal.clone(),
-                                value: new_value,
-                            }
-                        }
-                        _ => {
-                            new_field = field.clone();
-                        }
+                None => expression,
+            },
+            false => expression,
+        };
+        match expression {
+            Expression::Number(token) if options.shorten_numbers => {
+                let TokenType::Number { text } = token.token_type() else {
+                    unreachable!("number expressions hold number tokens")
+                };
+                match shorten_number(text, options.target) {
+                    Some(shortest) => {
+                        trace!("Shortened {} to {}", text, shortest);
+                        let token_type = TokenType::Number {
+                            text: shortest.into(),
+                        };
+                        Expression::Number(token.with_token(Token::new(token_type)))
                     }
-                    new_fields.push(Pair::new(new_field, None));
+                    None => Expression::Number(token),
                 }
-                new_fields = punctuator::punctuate_name(new_fields, &COMMA);
-                let new_x = x.clone().with_fields(new_fields);
-                full_moon::ast::FunctionArgs::TableConstructor(new_x)
             }
-            _ => function_args.clone(),
+            Expression::String(token) => Expression::String(self.string(token)),
+            expression => expression,
+        }
+    }
+
+    fn visit_function_args_end(&mut self, args: FunctionArgs) -> FunctionArgs {
+        match args {
+            FunctionArgs::String(token) if self.depth == 0 => {
+                FunctionArgs::String(self.string(token))
+            }
+            args => args,
+        }
+    }
+}
+
+impl Expressions<'_, '_> {
+    /// Rewrites a string literal in its shortest form, or only with ASCII characters
+    fn string(&mut self, token: TokenReference) -> TokenReference {
+        let options = self.minifier.options;
+        if !options.shorten_strings && !options.ascii_only {
+            return token;
+        }
+        match shorten_string(token.token(), options.target, options.ascii_only) {
+            Some(token_type) => {
+                let shortest = token.with_token(Token::new(token_type));
+                trace!("Rewrote {} as {}", token.token(), shortest.token());
+                shortest
+            }
+            None => token,
+        }
+    }
+}
+
+pub(crate) struct Minifier<'a> {
+    code: &'a str,
+    options: &'a Options,
+    /// Minified names keyed by the byte offset of each renamed identifier token
+    renames: HashMap<usize, String>,
+    /// Local bindings keyed by the byte offset of each identifier token naming them
+    bindings: HashMap<usize, usize>,
+    /// Statements merged into the statement before them so far
+    merged_statements: usize,
+    /// Operators replaced by their value so far
+    folded_constants: usize,
+    /// Unreachable statements and dead branches removed so far
+    removed_statements: usize,
+}
+
+impl<'a> Minifier<'a> {
+    pub fn new(code: &'a str, options: &'a Options) -> Self {
+        Minifier {
+            code,
+            options,
+            renames: HashMap::new(),
+            bindings: HashMap::new(),
+            merged_statements: 0,
+            folded_constants: 0,
+            removed_statements: 0,
+        }
+    }
+
+    /// Folds constants in an expression and minifies the functions nested in it, the only place
+    /// where an expression can contain statements
+    fn minify_expression<T: VisitMut>(&mut self, node: T) -> T {
+        node.visit_mut(&mut Expressions {
+            minifier: self,
+            depth: 0,
+        })
+    }
+
+    /// Minifies the block of a local function, function declaration or anonymous function.
+    /// Parameters, `self`, `...`, generics and type annotations are kept as they are.
+    fn minify_function_body(&mut self, body: &FunctionBody) -> FunctionBody {
+        body.clone().with_block(self.minify_block(body.block()))
+    }
+
+    fn minify_stmt(&mut self, stmt: &Stmt) -> Stmt {
+        match stmt {
+            Stmt::Do(x) => Stmt::Do(x.clone().with_block(self.minify_block(x.block()))),
+            Stmt::While(x) => Stmt::While(
+                x.clone()
+                    .with_condition(self.minify_expression(x.condition().clone()))
+                    .with_block(self.minify_block(x.block())),
+            ),
+            Stmt::Repeat(x) => Stmt::Repeat(
+                x.clone()
+                    .with_block(self.minify_block(x.block()))
+                    .with_until(self.minify_expression(x.until().clone())),
+            ),
+            Stmt::If(x) => {
+                let condition = self.minify_expression(x.condition().clone());
+                let block = self.minify_block(x.block());
+                let else_if = x.else_if().map(|else_ifs| {
+                    else_ifs
+                        .iter()
+                        .map(|else_if| {
+                            let condition = self.minify_expression(else_if.condition().clone());
+                            let block = self.minify_block(else_if.block());
+                            else_if.clone().with_condition(condition).with_block(block)
+                        })
+                        .collect::<Vec<ElseIf>>()
+                });
+                let else_block = x.else_block().map(|block| self.minify_block(block));
+                Stmt::If(
+                    x.clone()
+                        .with_condition(condition)
+                        .with_block(block)
+                        .with_else_if(else_if)
+                        .with_else(else_block),
+                )
+            }
+            Stmt::NumericFor(x) => {
+                let start = self.minify_expression(x.start().clone());
+                let end = self.minify_expression(x.end().clone());
+                let step = x.step().map(|step| self.minify_expression(step.clone()));
+                Stmt::NumericFor(
+                    x.clone()
+                        .with_start(start)
+                        .with_end(end)
+                        .with_step(step)
+                        .with_block(self.minify_block(x.block())),
+                )
+            }
+            Stmt::GenericFor(x) => {
+                let expressions = self.minify_expression(x.expressions().clone());
+                Stmt::GenericFor(
+                    x.clone()
+                        .with_expressions(expressions)
+                        .with_block(self.minify_block(x.block())),
+                )
+            }
+            Stmt::LocalFunction(x) => {
+                Stmt::LocalFunction(x.clone().with_body(self.minify_function_body(x.body())))
+            }
+            Stmt::FunctionDeclaration(x) => {
+                Stmt::FunctionDeclaration(x.clone().with_body(self.minify_function_body(x.body())))
+            }
+            // The remaining statements have no blocks, so any statements in them are in
+            // anonymous functions
+            _ => self.minify_expression(stmt.clone()),
         }
     }
 
     fn minify_block(&mut self, block: &Block) -> Block {
-        let mut new_stmts: Vec<(Stmt, Option<TokenReference>)> = Vec::new();
-        // Local assignments`
-        let mut local_names: Punctuated<TokenReference> = Punctuated::new();
-        let mut local_expressions: Punctuated<Expression> = Punctuated::new();
-        let mut local_types: Vec<Option<TypeSpecifier>> = Vec::new();
-        // Assignments
-        let mut global_vars: Punctuated<Var> = Punctuated::new();
-        let mut global_expressions: Punctuated<Expression> = Punctuated::new();
+        let stmts = block
+            .stmts()
+            .map(|stmt| (self.minify_stmt(stmt), None))
+            .collect();
+        let last_stmt = block
+            .last_stmt()
+            .map(|last_stmt| (self.minify_expression(last_stmt.clone()), None));
+        let mut block = block.clone().with_stmts(stmts).with_last_stmt(last_stmt);
+        if self.options.remove_dead_code {
+            let removed;
+            (block, removed) = dead_code::eliminate(block, self.options.target);
+            self.removed_statements += removed;
+        }
+        let mut new_stmts = Vec::new();
+        let mut pending: Option<Merge> = None;
         for stmt in block.stmts() {
-            match stmt {
-                Stmt::LocalAssignment(x) => {
-                    println!("Found local assignment {:?}", x.names());
-                    let name_c = x.names().clone();
-                    let exp_c = x.expressions().clone();
-                    let diff_len = name_c.len() - exp_c.len();
-                    for name in name_c {
-                        //println!("{:#?}", name);
-                        local_names.push(Pair::new(whitespace::trim(&name), None));
-                    }
-                    for exp in exp_c {
-                        println!("loop");
-                        local_expressions.push(Pair::new(whitespace::trim_exp(&exp), None));
-                    }
-                    println!("{} diff len between name value", diff_len);
-                    if diff_len > 0 {
-                        for _ in 0..diff_len {
-                            local_expressions.push(Pair::new(NIL_SYMBOL.clone(), None));
-                        }
-                    }
-                    for var_type in x.type_specifiers() {
-                        //println!("{:#?}", var_type);
-                        //println!("{:#?}", var_type.cloned());
-                        local_types.push(var_type.cloned());
-                    }
-                }
-                Stmt::Assignment(x) => {
-                    //println!("{:?}", x);
-                    //println!("Found assignment {:?}", x);
-                    println!("Found global var assignment {:?}", x);
-                    for var in x.variables().clone() {
-                        //println!("{:#?}", var.tokens());
-                        let y_out: Var;
-                        match var {
-                            Var::Name(ref y) => match y.token_type() {
-                                TokenType::Identifier { identifier } => {
-                                    let name_str = identifier.as_str();
-                                    println!(
-                                        "Mapping global variable '{}' to minified name",
-                                        name_str
-                                    );
-                                    if !self.global_names.contains_key(name_str) {
-                                        utils::generate_shortest_names(
-                                            &mut self.global_names,
-                                            name_str.to_string(),
-                                        );
-                                    }
-                                    let new_name_str = self.global_names.get(name_str).unwrap();
-                                    let new_token = TokenReference::new(
-                                        vec![],
-                                        Token::new(TokenType::Identifier {
-                                            identifier: new_name_str.clone().into(),
-                                        }),
-                                        vec![],
-                                    );
-                                    y_out = Var::Name(new_token);
-                                }
-                                _ => {
-                                    y_out = Var::Name(whitespace::trim(y));
-                                }
-                            },
-                            Var::Expression(y) => {
-                                let new_prefix = whitespace::trim_prefix(y.prefix());
-                                let mut new_suffixes: Vec<Suffix> = Vec::new();
-                                for suffix in y.suffixes() {
-                                    new_suffixes.push(whitespace::trim_suffix(self, suffix));
-                                }
-                                let new_y = y
-                                    .clone()
-                                    .with_prefix(new_prefix)
-                                    .with_suffixes(new_suffixes);
-                                y_out = Var::Expression(Box::new(new_y));
-                            }
-                            _ => {
-                                y_out = var.clone();
-                            }
-                        }
-                        global_vars.push(Pair::new(y_out, None));
-                    }
-                    for exp in x.expressions().clone() {
-                        let new_exp = whitespace::trim_exp(&exp);
-                        println!("Pushing global expression: {:#?}", new_exp);
-                        global_expressions.push(Pair::new(new_exp, None))
-                    }
-                }
-                Stmt::LocalFunction(x) => {
-                    let body = x.body();
-                    let params_parentheses = body.parameters_parentheses();
-                    let new_parentheses = whitespace::trim_cspan(params_parentheses);
-                    let mut new_params: Punctuated<Parameter> = Punctuated::new();
-                    for param in body.parameters() {
-                        match param {
-                            Parameter::Ellipsis(x) => {
-                                let new_param = Parameter::Ellipsis(whitespace::trim(x));
-                                new_params.push(Pair::new(new_param, None));
-                            }
-                            Parameter::Name(x) => {
-                                let new_param = Parameter::Ellipsis(whitespace::trim(x));
-                                new_params.push(Pair::new(new_param, None));
-                            }
-                            _ => {}
-                        }
-                    }
-                    new_params = punctuator::punctuate_name(new_params, &*COMMA);
-                    let new_local = whitespace::trim_leading(x.local_token());
-                    let new_end = whitespace::trim(body.end_token());
-                    let new_body = body
-                        .clone()
-                        .with_parameters_parentheses(new_parentheses)
-                        .with_parameters(new_params)
-                        .with_block(self.minify_block(body.block()))
-                        .with_end_token(new_end);
-                    let new_x = x
-                        .clone()
-                        .with_function_token(whitespace::append(
-                            &whitespace::trim_leading(x.function_token()),
-                            true,
-                            true,
-                        ))
-                        .with_local_token(new_local)
-                        .with_body(new_body);
-                    new_stmts.push((
-                        full_moon::ast::Stmt::LocalFunction(new_x),
-                        SEMICOLON.clone(),
-                    ))
-                }
-                Stmt::FunctionDeclaration(x) => {
-                    let body = x.body();
-                    let params_parentheses = body.parameters_parentheses();
-                    let new_parentheses = whitespace::trim_cspan(params_parentheses);
-                    let mut new_params: Punctuated<Parameter> = Punctuated::new();
-                    for param in body.parameters() {
-                        match param {
-                            Parameter::Ellipsis(x) => {
-                                let new_param = Parameter::Ellipsis(whitespace::trim(x));
-                                new_params.push(Pair::new(new_param, None));
-                            }
-                            Parameter::Name(x) => {
-                                let new_param = Parameter::Ellipsis(whitespace::trim(x));
-                                new_params.push(Pair::new(new_param, None));
-                            }
-                            _ => {}
-                        }
-                    }
-                    new_params = punctuator::punctuate_name(new_params, &COMMA);
-                    let new_end = whitespace::trim(body.end_token());
-                    let new_body = body
-                        .clone()
-                        .with_parameters_parentheses(new_parentheses)
-                        .with_parameters(new_params)
-                        .with_block(self.minify_block(body.block()))
-                        .with_end_token(new_end);
-                    let new_x = x
-                        .clone()
-                        .with_function_token(whitespace::append(
-                            &whitespace::trim_leading(x.function_token()),
-                            false,
-                            true,
-                        ))
-                        .with_body(new_body);
-                    new_stmts.push((
-                        full_moon::ast::Stmt::FunctionDeclaration(new_x),
-                        SEMICOLON.clone(),
-                    ))
-                }
-                Stmt::FunctionCall(x) => {
-                    // println!("{:#?}", x);
-                    let new_prefix = whitespace::trim_prefix(x.prefix());
-                    let mut new_suffixes: Vec<Suffix> = Vec::new();
-                    for suffix in x.suffixes() {
-                        new_suffixes.push(whitespace::trim_suffix(self, suffix));
-                    }
-                    let new_x = x
-                        .clone()
-                        .with_prefix(new_prefix)
-                        .with_suffixes(new_suffixes);
-                    new_stmts.push((full_moon::ast::Stmt::FunctionCall(new_x), SEMICOLON.clone()))
-                }
-                _ => {
-                    // TODO: remove whitespaces
-                    new_stmts.push((stmt.clone(), SEMICOLON.clone()))
+            if let Some(merge) = &mut pending {
+                if merge.push(self, stmt) {
+                    self.merged_statements += 1;
+                    trace!(
+                        "Merged statement into the previous one: {}",
+                        stmt.to_string().trim()
+                    );
+                    continue;
                 }
             }
+            if let Some(merge) = pending.take() {
+                new_stmts.push((merge.into_stmt(), None));
+            }
+            if self.options.merge_statements {
+                pending = Merge::new(self, stmt);
+            }
+            if pending.is_none() {
+                new_stmts.push((stmt.clone(), None));
+            }
         }
-        if local_names.len() > 0 {
-            let local_names = punctuator::punctuate_name(local_names, &COMMA);
-            let local_assignments = LocalAssignment::new(local_names.clone())
-                .with_names(local_names)
-                //.with_type_specifiers(local_types)
-                .with_equal_token(EQ_TOKEN.clone())
-                .with_expressions(punctuator::punctuate_exp(local_expressions, &COMMA));
-            new_stmts.splice(
-                0..0,
-                vec![(
-                    full_moon::ast::Stmt::LocalAssignment(local_assignments),
-                    None,
-                )]
-                .iter()
-                .cloned(),
-            );
-        }
-        if global_vars.len() > 0 {
-            let assignments = Assignment::new(
-                punctuator::punctuate_name(global_vars, &COMMA),
-                punctuator::punctuate_exp(global_expressions, &COMMA),
-            )
-            .with_equal_token(EQ_TOKEN.as_ref().unwrap().clone());
-            new_stmts.splice(
-                0..0,
-                vec![(full_moon::ast::Stmt::Assignment(assignments), None)]
-                    .iter()
-                    .cloned(),
-            );
+        if let Some(merge) = pending {
+            new_stmts.push((merge.into_stmt(), None));
         }
-        block.clone().with_stmts(new_stmts)
+        block.with_stmts(new_stmts)
     }
 
-    pub fn minify(&mut self) -> String {
-        let ast = full_moon::parse(self.code.as_str()).expect("parse lua script error");
+    pub fn minify(&mut self) -> Result<Output, Error> {
+        let ast = full_moon::parse_fallible(self.code, self.options.dialect.lua_version())
+            .into_result()
+            .map_err(|errors| Error::Parse(errors.into_iter().map(ParseError::from).collect()))?;
+        // Stripping types may remove the statement these comments are attached to
+        let mut header = header::header(&ast);
+        let ast = match self.options.types {
+            TypeMode::Keep => ast,
+            TypeMode::Strip => {
+                debug!("Stripping type syntax");
+                TypeStripper.visit_ast(ast)
+            }
+        };
         let block = ast.nodes();
+        let mut scopes = ScopeTree::build(block)?;
+        scopes.assign_names(self.options);
+        self.renames = scopes.renames();
+        self.bindings = scopes.binding_positions();
+        debug!(
+            "Found {} scopes, {} local bindings and {} globals",
+            scopes.scopes.len(),
+            scopes.bindings.len(),
+            scopes.globals.len()
+        );
+        let locals = scopes.bindings.iter().filter_map(|binding| {
+            Some(Rename {
+                kind: RenameKind::Local,
+                original: binding.name.clone(),
+                renamed: binding.renamed.clone()?,
+                positions: binding.positions.clone(),
+                output_positions: Vec::new(),
+            })
+        });
+        let globals = scopes.globals.iter().filter_map(|global| {
+            Some(Rename {
+                kind: RenameKind::Global,
+                original: global.name.clone(),
+                renamed: global.renamed.clone()?,
+                positions: global.positions.clone(),
+                output_positions: Vec::new(),
+            })
+        });
+        let mut renames: Vec<Rename> = locals.chain(globals).collect();
+        for rename in renames
+            .iter()
+            .filter(|rename| rename.kind == RenameKind::Global)
+        {
+            debug!("Renamed global {} to {}", rename.original, rename.renamed);
+        }
         let new_block = self.minify_block(block);
-        let new_ast = ast.clone().with_nodes(new_block);
-        format!("-- Minified by luamine-rs v{}\n-- Code may not be usable, report bugs at: https://github.com/teppyboy/luamine-rs/issues\n{}", env!("CARGO_PKG_VERSION"), &new_ast)
+        target::check(&new_block, self.options.target)?;
+        // Origins are also where the renamed names end up in the output
+        let mut emitter = Emitter::new(&self.renames)
+            .ascii_only(self.options.ascii_only.then_some(self.options.target))
+            .track_origins();
+        emitter.block(&new_block);
+        let (body, mut origins) = emitter.finish_with_origins();
+        if let Some(text) = &self.options.banner {
+            header.push_str(&header::banner(text));
+        }
+        for origin in &mut origins {
+            origin.output += header.len();
+        }
+        let code = header + &body;
+        let renamed: HashMap<usize, usize> = origins
+            .iter()
+            .filter(|origin| origin.name.is_some())
+            .map(|origin| (origin.source, origin.output))
+            .collect();
+        for rename in &mut renames {
+            rename.output_positions = rename
+                .positions
+                .iter()
+                .filter_map(|position| renamed.get(position).copied())
+                .collect();
+        }
+        let source_map = self
+            .options
+            .source_map
+            .then(|| SourceMap::new(self.code, &code, &origins));
+        if self.options.verify {
+            verify::verify(self.code, &ast, &code, &self.renames, self.options)?;
+            debug!("Verified the minified code against the source");
+        }
+        let stats = Stats {
+            input_size: self.code.len(),
+            output_size: code.len(),
+            renamed_locals: renames
+                .iter()
+                .filter(|rename| rename.kind == RenameKind::Local)
+                .count(),
+            renamed_globals: renames
+                .iter()
+                .filter(|rename| rename.kind == RenameKind::Global)
+                .count(),
+            merged_statements: self.merged_statements,
+            folded_constants: self.folded_constants,
+            removed_statements: self.removed_statements,
+        };
+        Ok(Output {
+            code,
+            stats,
+            renames,
+            source_map,
+        })
     }
 }
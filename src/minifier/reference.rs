@@ -0,0 +1,309 @@
+use full_moon::{
+    ast::{BinOp, Expression, UnOp},
+    tokenizer::{Symbol, TokenType},
+};
+
+use crate::{
+    minifier::literals::{decode_string, parse_number, Number},
+    Target,
+};
+
+/// The value of a constant expression, as Lua computes it
+#[derive(Clone, Debug)]
+pub enum Constant {
+    Nil,
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    String(Vec<u8>),
+}
+
+impl Constant {
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Constant::Nil | Constant::Boolean(false))
+    }
+
+    /// Whether two values can't be told apart by a program, down to the subtype of numbers
+    pub fn same(&self, other: &Constant) -> bool {
+        match (self, other) {
+            (Constant::Nil, Constant::Nil) => true,
+            (Constant::Boolean(a), Constant::Boolean(b)) => a == b,
+            (Constant::Integer(a), Constant::Integer(b)) => a == b,
+            (Constant::Float(a), Constant::Float(b)) => {
+                a.to_bits() == b.to_bits() || (a.is_nan() && b.is_nan())
+            }
+            (Constant::String(a), Constant::String(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// Evaluates expressions made of literals and operators the way the Lua reference manual
+/// defines them. Verification checks the folded constants of the minifier against it, so it
+/// deliberately shares nothing with `fold` but the reading of literals.
+#[derive(Clone, Copy)]
+pub struct Reference {
+    pub target: Target,
+}
+
+impl Reference {
+    /// The value of an expression, `None` if it isn't constant, raises an error or depends on
+    /// the machine running it
+    pub fn evaluate(&self, expression: &Expression) -> Option<Constant> {
+        match expression {
+            Expression::Number(token) => match token.token_type() {
+                TokenType::Number { text } => Some(match parse_number(text, self.target)? {
+                    Number::Integer(integer) => Constant::Integer(integer),
+                    Number::Float(float) => Constant::Float(float),
+                }),
+                _ => None,
+            },
+            Expression::String(token) => {
+                decode_string(token.token(), self.target).map(Constant::String)
+            }
+            Expression::Symbol(token) => match token.token_type() {
+                TokenType::Symbol {
+                    symbol: Symbol::Nil,
+                } => Some(Constant::Nil),
+                TokenType::Symbol {
+                    symbol: Symbol::True,
+                } => Some(Constant::Boolean(true)),
+                TokenType::Symbol {
+                    symbol: Symbol::False,
+                } => Some(Constant::Boolean(false)),
+                _ => None,
+            },
+            Expression::Parentheses { expression, .. } => self.evaluate(expression),
+            Expression::UnaryOperator { unop, expression } => {
+                let operand = self.evaluate(expression)?;
+                match (unop, operand) {
+                    (UnOp::Not(_), operand) => Some(Constant::Boolean(!operand.is_truthy())),
+                    (UnOp::Minus(_), Constant::Integer(integer)) => {
+                        Some(Constant::Integer(integer.wrapping_neg()))
+                    }
+                    (UnOp::Minus(_), Constant::Float(float)) => Some(Constant::Float(-float)),
+                    (UnOp::Hash(_), Constant::String(bytes)) => Some(self.count(bytes.len())),
+                    _ => None,
+                }
+            }
+            Expression::BinaryOperator { lhs, binop, rhs } => match binop {
+                BinOp::And(_) => {
+                    let left = self.evaluate(lhs)?;
+                    match left.is_truthy() {
+                        true => self.evaluate(rhs),
+                        false => Some(left),
+                    }
+                }
+                BinOp::Or(_) => {
+                    let left = self.evaluate(lhs)?;
+                    match left.is_truthy() {
+                        true => Some(left),
+                        false => self.evaluate(rhs),
+                    }
+                }
+                _ => self.binary(binop, self.evaluate(lhs)?, self.evaluate(rhs)?),
+            },
+            _ => None,
+        }
+    }
+
+    /// A length, which is an integer on targets which have them
+    fn count(&self, count: usize) -> Constant {
+        match self.target.has_integers() {
+            true => Constant::Integer(count as i64),
+            false => Constant::Float(count as f64),
+        }
+    }
+
+    fn binary(&self, binop: &BinOp, left: Constant, right: Constant) -> Option<Constant> {
+        use Constant::{Boolean, Integer};
+        match binop {
+            BinOp::TwoEqual(_) => return equal(&left, &right).map(Boolean),
+            BinOp::TildeEqual(_) => return equal(&left, &right).map(|equal| Boolean(!equal)),
+            // Strings compare with the locale of the machine running the program
+            BinOp::LessThan(_) => return less(&left, &right, false).map(Boolean),
+            BinOp::LessThanEqual(_) => return less(&left, &right, true).map(Boolean),
+            BinOp::GreaterThan(_) => return less(&right, &left, false).map(Boolean),
+            BinOp::GreaterThanEqual(_) => return less(&right, &left, true).map(Boolean),
+            BinOp::TwoDots(_) => {
+                let mut bytes = self.concatenated(left)?;
+                bytes.extend(self.concatenated(right)?);
+                return Some(Constant::String(bytes));
+            }
+            _ => {}
+        }
+        // Strings coerced by arithmetic are left to the program
+        match (left, right) {
+            (Integer(a), Integer(b)) => match binop {
+                BinOp::Plus(_) => Some(Integer(a.wrapping_add(b))),
+                BinOp::Minus(_) => Some(Integer(a.wrapping_sub(b))),
+                BinOp::Star(_) => Some(Integer(a.wrapping_mul(b))),
+                // Dividing an integer by zero is an error
+                BinOp::DoubleSlash(_) if b != 0 => {
+                    let quotient = a.wrapping_div(b);
+                    match a.wrapping_rem(b) != 0 && (a < 0) != (b < 0) {
+                        true => Some(Integer(quotient - 1)),
+                        false => Some(Integer(quotient)),
+                    }
+                }
+                BinOp::Percent(_) if b != 0 => {
+                    let remainder = a.wrapping_rem(b);
+                    match remainder != 0 && (remainder < 0) != (b < 0) {
+                        true => Some(Integer(remainder + b)),
+                        false => Some(Integer(remainder)),
+                    }
+                }
+                BinOp::Slash(_) | BinOp::Caret(_) => {
+                    self.float_arithmetic(binop, a as f64, b as f64)
+                }
+                _ => None,
+            },
+            (a, b) => self.float_arithmetic(binop, number(&a)?, number(&b)?),
+        }
+    }
+
+    fn float_arithmetic(&self, binop: &BinOp, a: f64, b: f64) -> Option<Constant> {
+        let result = match binop {
+            BinOp::Plus(_) => a + b,
+            BinOp::Minus(_) => a - b,
+            BinOp::Star(_) => a * b,
+            BinOp::Slash(_) => a / b,
+            BinOp::Caret(_) => a.powf(b),
+            BinOp::DoubleSlash(_) => (a / b).floor(),
+            // Lua 5.3 takes the C remainder and moves it to the sign of the divisor
+            BinOp::Percent(_) if self.target.has_integers() => {
+                let remainder = a % b;
+                match remainder != 0.0 && (remainder < 0.0) != (b < 0.0) {
+                    true => remainder + b,
+                    false => remainder,
+                }
+            }
+            BinOp::Percent(_) => a - (a / b).floor() * b,
+            _ => return None,
+        };
+        Some(Constant::Float(result))
+    }
+
+    /// What `..` turns a value into, `None` for floats whose digits differ between versions
+    fn concatenated(&self, value: Constant) -> Option<Vec<u8>> {
+        match value {
+            Constant::String(bytes) => Some(bytes),
+            Constant::Integer(integer) => Some(integer.to_string().into_bytes()),
+            Constant::Float(float) if float.fract() == 0.0 && float.abs() < 1e14 => {
+                let sign = if float.is_sign_negative() { "-" } else { "" };
+                let digits = format!("{}{}", sign, float.abs() as i64);
+                Some(match self.target.has_integers() {
+                    true => format!("{}.0", digits).into_bytes(),
+                    false => digits.into_bytes(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A number converted to a float the way arithmetic does, rounding large integers
+fn number(value: &Constant) -> Option<f64> {
+    match *value {
+        Constant::Integer(integer) => Some(integer as f64),
+        Constant::Float(float) => Some(float),
+        _ => None,
+    }
+}
+
+/// Integers which a float holds exactly
+const EXACT: i64 = 1 << 53;
+
+/// A number as a float, `None` if that would round it
+fn to_float(value: &Constant) -> Option<f64> {
+    match *value {
+        Constant::Integer(integer) if (-EXACT..=EXACT).contains(&integer) => Some(integer as f64),
+        Constant::Float(float) => Some(float),
+        _ => None,
+    }
+}
+
+fn equal(left: &Constant, right: &Constant) -> Option<bool> {
+    match (left, right) {
+        (Constant::Integer(a), Constant::Integer(b)) => Some(a == b),
+        (Constant::Integer(_) | Constant::Float(_), Constant::Integer(_) | Constant::Float(_)) => {
+            Some(to_float(left)? == to_float(right)?)
+        }
+        (Constant::String(a), Constant::String(b)) => Some(a == b),
+        (Constant::Boolean(a), Constant::Boolean(b)) => Some(a == b),
+        (Constant::Nil, Constant::Nil) => Some(true),
+        _ => Some(false),
+    }
+}
+
+/// `left < right`, or `left <= right` with `or_equal`, for numbers only
+fn less(left: &Constant, right: &Constant, or_equal: bool) -> Option<bool> {
+    let (a, b) = match (left, right) {
+        (Constant::Integer(a), Constant::Integer(b)) => {
+            return Some(if or_equal { a <= b } else { a < b })
+        }
+        (Constant::Integer(_) | Constant::Float(_), Constant::Integer(_) | Constant::Float(_)) => {
+            (to_float(left)?, to_float(right)?)
+        }
+        _ => return None,
+    };
+    Some(if or_equal { a <= b } else { a < b })
+}
+
+#[cfg(test)]
+mod tests {
+    use full_moon::ast::LastStmt;
+
+    use super::*;
+
+    /// The value of the expression `source` returns
+    fn value(target: Target, source: &str) -> Option<Constant> {
+        let ast = full_moon::parse_fallible(&format!("return {source}"), target.lua_version())
+            .into_result()
+            .unwrap();
+        let Some(LastStmt::Return(x)) = ast.nodes().last_stmt() else {
+            unreachable!()
+        };
+        Reference { target }.evaluate(x.returns().iter().next().unwrap())
+    }
+
+    fn same(target: Target, source: &str, expected: Constant) -> bool {
+        value(target, source).is_some_and(|value| value.same(&expected))
+    }
+
+    #[test]
+    fn integers_and_floats() {
+        use Constant::{Float, Integer};
+        assert!(same(Target::Lua54, "1 + 2", Integer(3)));
+        assert!(same(Target::Lua54, "1 + 2.0", Float(3.0)));
+        assert!(same(Target::Lua54, "7 // 2", Integer(3)));
+        assert!(same(Target::Lua54, "-7 // 2", Integer(-4)));
+        assert!(same(Target::Lua54, "-7 % 3", Integer(2)));
+        assert!(same(Target::Lua54, "3 / 2", Float(1.5)));
+        assert!(same(Target::Lua51, "1 + 2", Float(3.0)));
+        assert!(same(Target::Lua51, "-7 % 3", Float(2.0)));
+        assert!(same(Target::Luau, "#'abc'", Float(3.0)));
+        assert!(same(Target::Lua53, "#'abc'", Integer(3)));
+    }
+
+    #[test]
+    fn errors_are_left_to_the_program() {
+        assert!(value(Target::Lua54, "1 // 0").is_none());
+        assert!(value(Target::Lua54, "1 % 0").is_none());
+        assert!(value(Target::Lua54, "'1' + 1").is_none());
+        assert!(value(Target::Lua54, "'a' < 'b'").is_none());
+        assert!(value(Target::Lua54, "x + 1").is_none());
+    }
+
+    #[test]
+    fn logic_and_strings() {
+        use Constant::{Boolean, Nil, String};
+        assert!(same(Target::Luau, "not nil", Boolean(true)));
+        assert!(same(Target::Luau, "nil and x", Nil));
+        assert!(same(Target::Luau, "1 == 1.0", Boolean(true)));
+        assert!(same(Target::Luau, "'a' .. 1", String(b"a1".to_vec())));
+        assert!(same(Target::Lua54, "'a' .. 1.0", String(b"a1.0".to_vec())));
+        assert!(same(Target::Lua51, "'a' .. 1.0", String(b"a1".to_vec())));
+        assert!(value(Target::Luau, "'a' .. 0.1").is_none());
+    }
+}
@@ -0,0 +1,700 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use full_moon::{
+    ast::{
+        luau::{GenericDeclaration, IndexedTypeInfo, TypeFieldKey, TypeInfo, TypeSpecifier},
+        Block, Call, Expression, Field, FunctionArgs, FunctionBody, FunctionCall, Index, LastStmt,
+        Parameter, Prefix, Stmt, Suffix, Var,
+    },
+    node::Node,
+    tokenizer::{TokenReference, TokenType},
+};
+
+use crate::{
+    minifier::{constants::BUILTIN_GLOBALS, utils},
+    Error, Options,
+};
+
+/// A local variable, function parameter or loop variable
+pub struct Binding {
+    /// The name the binding is declared with in the source
+    pub name: String,
+    /// Byte offsets of the declaration and of every reference to the binding
+    pub positions: Vec<usize>,
+    /// The shortest free name picked by `ScopeTree::assign_names`
+    pub renamed: Option<String>,
+    /// Bindings which keep their names, e.g. the implicit `self` or every local when
+    /// `Options::rename_locals` is off
+    fixed: bool,
+    /// Bindings which would shadow this one (or be shadowed by it) if they shared a name
+    conflicts: HashSet<usize>,
+    /// Globals referenced while this binding was in scope
+    globals: HashSet<usize>,
+}
+
+/// A block which can declare local bindings
+pub struct Scope {
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub bindings: Vec<usize>,
+    /// The newest binding declared in this scope for each name
+    names: HashMap<String, usize>,
+}
+
+/// A name which isn't declared by any enclosing scope
+pub struct Global {
+    pub name: String,
+    /// Byte offsets of every reference to the global
+    pub positions: Vec<usize>,
+    /// Whether the chunk assigns to the global or declares a function with its name
+    pub written: bool,
+    /// The new name of the global, only set when globals are mangled
+    pub renamed: Option<String>,
+}
+
+/// The scopes, bindings and free globals of a chunk
+pub struct ScopeTree {
+    pub scopes: Vec<Scope>,
+    pub bindings: Vec<Binding>,
+    pub globals: Vec<Global>,
+}
+
+impl ScopeTree {
+    /// Builds the scope tree of a chunk
+    pub fn build(block: &Block) -> Result<Self, Error> {
+        let mut analyzer = Analyzer {
+            tree: ScopeTree {
+                scopes: Vec::new(),
+                bindings: Vec::new(),
+                globals: Vec::new(),
+            },
+            global_ids: HashMap::new(),
+            scope_stack: Vec::new(),
+            active: Vec::new(),
+            active_index: Vec::new(),
+            unsupported: None,
+        };
+        analyzer.push_scope();
+        analyzer.block(block);
+        analyzer.pop_scope();
+        match analyzer.unsupported {
+            Some(error) => Err(error),
+            None => Ok(analyzer.tree),
+        }
+    }
+
+    /// Gives every binding the shortest name that doesn't collide with anything it can see.
+    /// Bindings with the most references are named first so they get the shortest names.
+    ///
+    /// When `mangle_globals` is set, globals which the chunk writes to are renamed as well,
+    /// unless they are builtins or listed in `keep_globals`. Globals which are only read must
+    /// be defined by someone else, so they always keep their names.
+    pub fn assign_names(&mut self, options: &Options) {
+        if !options.rename_locals {
+            for binding in &mut self.bindings {
+                binding.fixed = true;
+            }
+        }
+        let mangled: Vec<bool> = self
+            .globals
+            .iter()
+            .map(|global| {
+                options.mangle_globals
+                    && global.written
+                    && !options.keep_globals.contains(&global.name)
+                    && !BUILTIN_GLOBALS.contains(&global.name.as_str())
+            })
+            .collect();
+        // Locals which are in scope at some reference to each global
+        let mut shadowing: Vec<Vec<usize>> = vec![Vec::new(); self.globals.len()];
+        for (id, binding) in self.bindings.iter().enumerate() {
+            for &global in &binding.globals {
+                shadowing[global].push(id);
+            }
+        }
+
+        let mut order: Vec<Name> = (0..self.bindings.len())
+            .filter(|&id| !self.bindings[id].fixed)
+            .map(Name::Local)
+            .chain(
+                (0..self.globals.len())
+                    .filter(|&id| mangled[id])
+                    .map(Name::Global),
+            )
+            .collect();
+        order.sort_by_key(|&name| {
+            std::cmp::Reverse(match name {
+                Name::Local(id) => self.bindings[id].positions.len(),
+                Name::Global(id) => self.globals[id].positions.len(),
+            })
+        });
+        for name in order {
+            let mut taken: HashSet<&str> = HashSet::new();
+            match name {
+                Name::Local(id) => {
+                    let binding = &self.bindings[id];
+                    for &global in &binding.globals {
+                        let global = &self.globals[global];
+                        match &global.renamed {
+                            Some(renamed) => taken.insert(renamed.as_str()),
+                            None => taken.insert(global.name.as_str()),
+                        };
+                    }
+                    for &other in &binding.conflicts {
+                        let other = &self.bindings[other];
+                        if other.fixed {
+                            taken.insert(other.name.as_str());
+                        } else if let Some(renamed) = &other.renamed {
+                            taken.insert(renamed.as_str());
+                        }
+                    }
+                }
+                Name::Global(id) => {
+                    // Globals live for the whole program, so they can't share a name with
+                    // any other global or builtin
+                    taken.extend(BUILTIN_GLOBALS.iter().copied());
+                    for (other, global) in self.globals.iter().enumerate() {
+                        match &global.renamed {
+                            Some(renamed) => taken.insert(renamed.as_str()),
+                            None if !mangled[other] => taken.insert(global.name.as_str()),
+                            None => false,
+                        };
+                    }
+                    for &local in &shadowing[id] {
+                        let local = &self.bindings[local];
+                        if local.fixed {
+                            taken.insert(local.name.as_str());
+                        } else if let Some(renamed) = &local.renamed {
+                            taken.insert(renamed.as_str());
+                        }
+                    }
+                }
+            }
+            let mut index = 0;
+            let new_name = loop {
+                let new_name = utils::generate_next_name(index);
+                if !utils::is_reserved(&new_name) && !taken.contains(new_name.as_str()) {
+                    break new_name;
+                }
+                index += 1;
+            };
+            match name {
+                Name::Local(id) => self.bindings[id].renamed = Some(new_name),
+                Name::Global(id) => self.globals[id].renamed = Some(new_name),
+            }
+        }
+    }
+
+    /// Maps the byte offset of every renamed identifier token to its new name
+    pub fn renames(&self) -> HashMap<usize, String> {
+        let mut renames = HashMap::new();
+        let bindings = self
+            .bindings
+            .iter()
+            .map(|binding| (&binding.positions, &binding.renamed));
+        let globals = self
+            .globals
+            .iter()
+            .map(|global| (&global.positions, &global.renamed));
+        for (positions, renamed) in bindings.chain(globals) {
+            if let Some(name) = renamed {
+                for &position in positions {
+                    renames.insert(position, name.clone());
+                }
+            }
+        }
+        renames
+    }
+
+    /// Maps the byte offset of every identifier token which names a local binding to the binding
+    pub fn binding_positions(&self) -> HashMap<usize, usize> {
+        let mut positions = HashMap::new();
+        for (id, binding) in self.bindings.iter().enumerate() {
+            for &position in &binding.positions {
+                positions.insert(position, id);
+            }
+        }
+        positions
+    }
+}
+
+/// Anything that can be renamed by `ScopeTree::assign_names`
+#[derive(Clone, Copy)]
+enum Name {
+    Local(usize),
+    Global(usize),
+}
+
+struct Analyzer {
+    tree: ScopeTree,
+    global_ids: HashMap<String, usize>,
+    /// Scopes enclosing the node being visited, innermost last
+    scope_stack: Vec<usize>,
+    /// Bindings currently in scope, in declaration order
+    active: Vec<usize>,
+    /// Index of each binding in `active`, valid while it is in scope
+    active_index: Vec<usize>,
+    /// The first construct we couldn't look into, renaming isn't safe if there is one
+    unsupported: Option<Error>,
+}
+
+fn identifier(token: &TokenReference) -> Option<&str> {
+    match token.token_type() {
+        TokenType::Identifier { identifier } => Some(identifier.as_str()),
+        _ => None,
+    }
+}
+
+impl Analyzer {
+    fn unsupported(&mut self, node: &(impl Node + fmt::Display), kind: &str) {
+        if self.unsupported.is_none() {
+            let position = Node::start_position(node).unwrap_or_default();
+            self.unsupported = Some(Error::Unsupported {
+                message: format!("unsupported {}: {}", kind, node.to_string().trim()),
+                position: position.into(),
+            });
+        }
+    }
+
+    fn push_scope(&mut self) {
+        let id = self.tree.scopes.len();
+        let parent = self.scope_stack.last().copied();
+        if let Some(parent) = parent {
+            self.tree.scopes[parent].children.push(id);
+        }
+        self.tree.scopes.push(Scope {
+            parent,
+            children: Vec::new(),
+            bindings: Vec::new(),
+            names: HashMap::new(),
+        });
+        self.scope_stack.push(id);
+    }
+
+    fn pop_scope(&mut self) {
+        let id = self.scope_stack.pop().unwrap();
+        let declared = self.tree.scopes[id].bindings.len();
+        self.active.truncate(self.active.len() - declared);
+    }
+
+    fn declare_named(&mut self, name: &str, position: Option<usize>, fixed: bool) {
+        let id = self.tree.bindings.len();
+        let scope = *self.scope_stack.last().unwrap();
+        self.tree.bindings.push(Binding {
+            name: name.to_string(),
+            positions: position.into_iter().collect(),
+            renamed: None,
+            fixed,
+            conflicts: HashSet::new(),
+            globals: HashSet::new(),
+        });
+        self.tree.scopes[scope].bindings.push(id);
+        self.tree.scopes[scope].names.insert(name.to_string(), id);
+        self.active_index.push(self.active.len());
+        self.active.push(id);
+    }
+
+    fn declare(&mut self, token: &TokenReference) {
+        if let Some(name) = identifier(token) {
+            // `_ENV` decides where globals resolve in Lua 5.2+, so it keeps its name
+            let fixed = name == "_ENV";
+            self.declare_named(name, Some(token.token().start_position().bytes()), fixed);
+        }
+    }
+
+    fn resolve(&self, name: &str) -> Option<usize> {
+        let mut scope = self.scope_stack.last().copied();
+        while let Some(id) = scope {
+            if let Some(&binding) = self.tree.scopes[id].names.get(name) {
+                return Some(binding);
+            }
+            scope = self.tree.scopes[id].parent;
+        }
+        None
+    }
+
+    fn reference(&mut self, token: &TokenReference) {
+        self.access(token, false);
+    }
+
+    fn assign(&mut self, token: &TokenReference) {
+        self.access(token, true);
+    }
+
+    fn access(&mut self, token: &TokenReference, write: bool) {
+        let Some(name) = identifier(token) else {
+            return;
+        };
+        let position = token.token().start_position().bytes();
+        match self.resolve(name) {
+            Some(id) => {
+                self.tree.bindings[id].positions.push(position);
+                // Anything declared after the binding and still in scope here would shadow it
+                for &other in &self.active[self.active_index[id] + 1..] {
+                    self.tree.bindings[id].conflicts.insert(other);
+                    self.tree.bindings[other].conflicts.insert(id);
+                }
+            }
+            None => {
+                let global = match self.global_ids.get(name) {
+                    Some(&global) => global,
+                    None => {
+                        let global = self.tree.globals.len();
+                        self.tree.globals.push(Global {
+                            name: name.to_string(),
+                            positions: Vec::new(),
+                            written: false,
+                            renamed: None,
+                        });
+                        self.global_ids.insert(name.to_string(), global);
+                        global
+                    }
+                };
+                self.tree.globals[global].positions.push(position);
+                self.tree.globals[global].written |= write;
+                for &id in &self.active {
+                    self.tree.bindings[id].globals.insert(global);
+                }
+            }
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        for stmt in block.stmts() {
+            self.stmt(stmt);
+        }
+        if let Some(LastStmt::Return(x)) = block.last_stmt() {
+            for exp in x.returns() {
+                self.expression(exp);
+            }
+        }
+    }
+
+    fn scoped_block(&mut self, block: &Block) {
+        self.push_scope();
+        self.block(block);
+        self.pop_scope();
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Assignment(x) => {
+                for exp in x.expressions() {
+                    self.expression(exp);
+                }
+                for var in x.variables() {
+                    self.assign_var(var);
+                }
+            }
+            Stmt::Do(x) => self.scoped_block(x.block()),
+            Stmt::FunctionCall(x) => self.function_call(x),
+            Stmt::FunctionDeclaration(x) => {
+                let names = x.name().names();
+                if let Some(name) = names.iter().next() {
+                    if names.len() == 1 && x.name().method_name().is_none() {
+                        self.assign(name);
+                    } else {
+                        self.reference(name);
+                    }
+                }
+                self.function_body(x.body(), x.name().method_name().is_some());
+            }
+            Stmt::GenericFor(x) => {
+                for exp in x.expressions() {
+                    self.expression(exp);
+                }
+                self.push_scope();
+                for name in x.names() {
+                    self.declare(name);
+                }
+                for type_specifier in x.type_specifiers().flatten() {
+                    self.type_specifier(type_specifier);
+                }
+                self.block(x.block());
+                self.pop_scope();
+            }
+            Stmt::If(x) => {
+                self.expression(x.condition());
+                self.scoped_block(x.block());
+                for else_if in x.else_if().into_iter().flatten() {
+                    self.expression(else_if.condition());
+                    self.scoped_block(else_if.block());
+                }
+                if let Some(block) = x.else_block() {
+                    self.scoped_block(block);
+                }
+            }
+            Stmt::LocalAssignment(x) => {
+                for exp in x.expressions() {
+                    self.expression(exp);
+                }
+                for type_specifier in x.type_specifiers().flatten() {
+                    self.type_specifier(type_specifier);
+                }
+                for name in x.names() {
+                    self.declare(name);
+                }
+            }
+            Stmt::LocalFunction(x) => {
+                self.declare(x.name());
+                self.function_body(x.body(), false);
+            }
+            Stmt::NumericFor(x) => {
+                self.expression(x.start());
+                self.expression(x.end());
+                if let Some(step) = x.step() {
+                    self.expression(step);
+                }
+                self.push_scope();
+                self.declare(x.index_variable());
+                if let Some(type_specifier) = x.type_specifier() {
+                    self.type_specifier(type_specifier);
+                }
+                self.block(x.block());
+                self.pop_scope();
+            }
+            Stmt::Repeat(x) => {
+                // The `until` condition can see the locals of the loop body
+                self.push_scope();
+                self.block(x.block());
+                self.expression(x.until());
+                self.pop_scope();
+            }
+            Stmt::While(x) => {
+                self.expression(x.condition());
+                self.scoped_block(x.block());
+            }
+            Stmt::CompoundAssignment(x) => {
+                self.assign_var(x.lhs());
+                self.expression(x.rhs());
+            }
+            Stmt::TypeDeclaration(x) => {
+                self.generics(x.generics());
+                self.type_info(x.type_definition());
+            }
+            Stmt::ExportedTypeDeclaration(x) => {
+                self.generics(x.type_declaration().generics());
+                self.type_info(x.type_declaration().type_definition());
+            }
+            // Type functions run in their own environment at type checking time, they can't see
+            // any of our bindings
+            _ => {}
+        }
+    }
+
+    fn function_body(&mut self, body: &FunctionBody, method: bool) {
+        self.push_scope();
+        self.generics(body.generics());
+        if method {
+            self.declare_named("self", None, true);
+        }
+        for param in body.parameters() {
+            if let Parameter::Name(name) = param {
+                self.declare(name);
+            }
+        }
+        for type_specifier in body.type_specifiers().flatten() {
+            self.type_specifier(type_specifier);
+        }
+        if let Some(return_type) = body.return_type() {
+            self.type_specifier(return_type);
+        }
+        self.block(body.block());
+        self.pop_scope();
+    }
+
+    fn function_call(&mut self, call: &FunctionCall) {
+        self.prefix(call.prefix());
+        for suffix in call.suffixes() {
+            self.suffix(suffix);
+        }
+    }
+
+    fn function_args(&mut self, args: &FunctionArgs) {
+        match args {
+            FunctionArgs::Parentheses { arguments, .. } => {
+                for exp in arguments {
+                    self.expression(exp);
+                }
+            }
+            FunctionArgs::TableConstructor(x) => self.fields(x.fields().iter()),
+            FunctionArgs::String(_) => {}
+            _ => self.unsupported(args, "function arguments"),
+        }
+    }
+
+    fn fields<'a>(&mut self, fields: impl Iterator<Item = &'a Field>) {
+        for field in fields {
+            match field {
+                Field::ExpressionKey { key, value, .. } => {
+                    self.expression(key);
+                    self.expression(value);
+                }
+                Field::NameKey { value, .. } => self.expression(value),
+                Field::NoKey(value) => self.expression(value),
+                _ => self.unsupported(field, "table field"),
+            }
+        }
+    }
+
+    fn prefix(&mut self, prefix: &Prefix) {
+        match prefix {
+            Prefix::Name(name) => self.reference(name),
+            Prefix::Expression(exp) => self.expression(exp),
+            _ => self.unsupported(prefix, "prefix"),
+        }
+    }
+
+    fn suffix(&mut self, suffix: &Suffix) {
+        match suffix {
+            Suffix::Call(Call::AnonymousCall(args)) => self.function_args(args),
+            Suffix::Call(Call::MethodCall(x)) => {
+                if let Some(type_instantiation) = x.type_instantiation() {
+                    for type_info in type_instantiation.types() {
+                        self.type_info(type_info);
+                    }
+                }
+                self.function_args(x.args());
+            }
+            Suffix::Index(Index::Brackets { expression, .. }) => self.expression(expression),
+            Suffix::TypeInstantiation(x) => {
+                for type_info in x.types() {
+                    self.type_info(type_info);
+                }
+            }
+            Suffix::Index(Index::Dot { .. }) => {}
+            _ => self.unsupported(suffix, "suffix"),
+        }
+    }
+
+    fn assign_var(&mut self, var: &Var) {
+        match var {
+            Var::Name(name) => self.assign(name),
+            _ => self.var(var),
+        }
+    }
+
+    fn var(&mut self, var: &Var) {
+        match var {
+            Var::Name(name) => self.reference(name),
+            Var::Expression(x) => {
+                self.prefix(x.prefix());
+                for suffix in x.suffixes() {
+                    self.suffix(suffix);
+                }
+            }
+            _ => self.unsupported(var, "variable"),
+        }
+    }
+
+    fn expression(&mut self, exp: &Expression) {
+        match exp {
+            Expression::BinaryOperator { lhs, rhs, .. } => {
+                self.expression(lhs);
+                self.expression(rhs);
+            }
+            Expression::Parentheses { expression, .. } => self.expression(expression),
+            Expression::UnaryOperator { expression, .. } => self.expression(expression),
+            Expression::Function(x) => self.function_body(x.body(), false),
+            Expression::FunctionCall(x) => self.function_call(x),
+            Expression::IfExpression(x) => {
+                self.expression(x.condition());
+                self.expression(x.if_expression());
+                for else_if in x.else_if_expressions().into_iter().flatten() {
+                    self.expression(else_if.condition());
+                    self.expression(else_if.expression());
+                }
+                self.expression(x.else_expression());
+            }
+            Expression::InterpolatedString(x) => {
+                for exp in x.expressions() {
+                    self.expression(exp);
+                }
+            }
+            Expression::TableConstructor(x) => self.fields(x.fields().iter()),
+            Expression::TypeAssertion {
+                expression,
+                type_assertion,
+            } => {
+                self.expression(expression);
+                self.type_info(type_assertion.cast_to());
+            }
+            Expression::Var(var) => self.var(var),
+            Expression::Number(_) | Expression::String(_) | Expression::Symbol(_) => {}
+            _ => self.unsupported(exp, "expression"),
+        }
+    }
+
+    fn generics(&mut self, generics: Option<&GenericDeclaration>) {
+        for generic in generics.into_iter().flat_map(|x| x.generics()) {
+            if let Some(default_type) = generic.default_type() {
+                self.type_info(default_type);
+            }
+        }
+    }
+
+    fn type_specifier(&mut self, type_specifier: &TypeSpecifier) {
+        self.type_info(type_specifier.type_info());
+    }
+
+    /// Types live in their own namespace, only `typeof(...)` can reference our bindings
+    fn type_info(&mut self, type_info: &TypeInfo) {
+        match type_info {
+            TypeInfo::Array { type_info, .. } => self.type_info(type_info),
+            TypeInfo::Callback {
+                generics,
+                arguments,
+                return_type,
+                ..
+            } => {
+                self.generics(generics.as_ref());
+                for argument in arguments {
+                    self.type_info(argument.type_info());
+                }
+                self.type_info(return_type);
+            }
+            TypeInfo::Generic { generics, .. } => {
+                for generic in generics {
+                    self.type_info(generic);
+                }
+            }
+            TypeInfo::Intersection(x) => {
+                for type_info in x.types() {
+                    self.type_info(type_info);
+                }
+            }
+            TypeInfo::Module { type_info, .. } => {
+                if let IndexedTypeInfo::Generic { generics, .. } = &**type_info {
+                    for generic in generics {
+                        self.type_info(generic);
+                    }
+                }
+            }
+            TypeInfo::Optional { base, .. } => self.type_info(base),
+            TypeInfo::Table { fields, .. } => {
+                for field in fields {
+                    if let TypeFieldKey::IndexSignature { inner, .. } = field.key() {
+                        self.type_info(inner);
+                    }
+                    self.type_info(field.value());
+                }
+            }
+            TypeInfo::Typeof { inner, .. } => self.expression(inner),
+            TypeInfo::Tuple { types, .. } => {
+                for type_info in types {
+                    self.type_info(type_info);
+                }
+            }
+            TypeInfo::Union(x) => {
+                for type_info in x.types() {
+                    self.type_info(type_info);
+                }
+            }
+            TypeInfo::Variadic { type_info, .. } => self.type_info(type_info),
+            _ => {}
+        }
+    }
+}
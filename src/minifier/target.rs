@@ -0,0 +1,263 @@
+use std::fmt;
+
+use full_moon::{
+    ast::{
+        lua52::{Goto, Label},
+        lua54::Attribute,
+        luau::{
+            ExportedTypeDeclaration, ExportedTypeFunction, GenericDeclaration, LuauAttribute,
+            TypeAssertion, TypeDeclaration, TypeFunction, TypeInstantiation, TypeSpecifier,
+        },
+        Block, CompoundAssignment, Expression, LastStmt,
+    },
+    node::Node,
+    tokenizer::{self, Token},
+    visitors::{Visit, Visitor},
+};
+
+use crate::{minifier::literals::decode_string, Error, Position, Target};
+
+/// Finds the first construct of the minified code which the target can't run
+struct Checker {
+    target: Target,
+    unsupported: Option<Error>,
+}
+
+impl Checker {
+    fn unsupported(&mut self, node: &(impl Node + fmt::Display), construct: &str) {
+        // Nodes the minifier built have no position, but the tokens inside them usually do
+        let position = node
+            .tokens()
+            .map(|token| token.token().start_position())
+            .find(|position| position.line() != 0);
+        self.unsupported_at(position, &node.to_string(), construct);
+    }
+
+    fn unsupported_at(
+        &mut self,
+        position: Option<tokenizer::Position>,
+        text: &str,
+        construct: &str,
+    ) {
+        if self.unsupported.is_some() {
+            return;
+        }
+        let position = position.filter(|position| position.line() != 0).map_or(
+            Position {
+                offset: 0,
+                line: 1,
+                column: 1,
+            },
+            Position::from,
+        );
+        self.unsupported = Some(Error::Unsupported {
+            message: format!(
+                "{} can't run on {}: {}",
+                construct,
+                self.target,
+                text.trim()
+            ),
+            position,
+        });
+    }
+
+    fn luau_only(&mut self, node: &(impl Node + fmt::Display), construct: &str) {
+        if self.target != Target::Luau {
+            self.unsupported(node, construct);
+        }
+    }
+}
+
+/// Whether a numeric literal is written in a way the target can read
+fn reads_number(text: &str, target: Target) -> bool {
+    let lower = text.to_ascii_lowercase();
+    let hex = lower.starts_with("0x");
+    if lower.contains('_') {
+        return target == Target::Luau;
+    }
+    if lower.starts_with("0b") && !matches!(target, Target::Luau | Target::LuaJit) {
+        return false;
+    }
+    // LuaJIT's 64-bit integer and imaginary suffixes
+    let digits = if hex { &lower[2..] } else { &lower };
+    if digits.ends_with(['l', 'i']) {
+        return target == Target::LuaJit;
+    }
+    // Hexadecimal fractions and binary exponents came with Lua 5.2
+    if hex && (lower.contains('.') || lower.contains('p')) {
+        return !matches!(target, Target::Lua51 | Target::Luau);
+    }
+    true
+}
+
+impl Visitor for Checker {
+    fn visit_expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::BinaryOperator { binop, .. } => {
+                let symbol = binop.token().token().to_string();
+                // `//` and the bitwise operators came with Lua 5.3, Luau only has `//`
+                let supported = match symbol.as_str() {
+                    "//" => matches!(self.target, Target::Lua53 | Target::Lua54 | Target::Luau),
+                    "&" | "|" | "~" | "<<" | ">>" => {
+                        matches!(self.target, Target::Lua53 | Target::Lua54)
+                    }
+                    _ => true,
+                };
+                if !supported {
+                    self.unsupported(expression, &format!("the `{}` operator", symbol));
+                }
+            }
+            Expression::UnaryOperator { unop, .. }
+                if unop.token().token().to_string() == "~"
+                    && !matches!(self.target, Target::Lua53 | Target::Lua54) =>
+            {
+                self.unsupported(expression, "the `~` operator");
+            }
+            Expression::IfExpression(_) => self.luau_only(expression, "an if expression"),
+            Expression::InterpolatedString(_) => {
+                self.luau_only(expression, "an interpolated string")
+            }
+            _ => {}
+        }
+    }
+
+    fn visit_number(&mut self, token: &Token) {
+        if !reads_number(&token.to_string(), self.target) {
+            let text = token.to_string();
+            self.unsupported_at(Some(token.start_position()), &text, "this number literal");
+        }
+    }
+
+    fn visit_string_literal(&mut self, token: &Token) {
+        if decode_string(token, Target::Luau).is_some()
+            && decode_string(token, self.target).is_none()
+        {
+            let text = token.to_string();
+            self.unsupported_at(
+                Some(token.start_position()),
+                &text,
+                "an escape of this string",
+            );
+        }
+    }
+
+    fn visit_last_stmt(&mut self, last_stmt: &LastStmt) {
+        if let LastStmt::Continue(_) = last_stmt {
+            self.luau_only(last_stmt, "`continue`");
+        }
+    }
+
+    fn visit_identifier(&mut self, token: &Token) {
+        // `goto` became a keyword in Lua 5.2, and LuaJIT reserves it too
+        if token.to_string() == "goto" && !matches!(self.target, Target::Lua51 | Target::Luau) {
+            self.unsupported_at(Some(token.start_position()), "goto", "the name `goto`");
+        }
+    }
+
+    fn visit_goto(&mut self, goto: &Goto) {
+        if matches!(self.target, Target::Lua51 | Target::Luau) {
+            self.unsupported(goto, "`goto`");
+        }
+    }
+
+    fn visit_label(&mut self, label: &Label) {
+        if matches!(self.target, Target::Lua51 | Target::Luau) {
+            self.unsupported(label, "a label");
+        }
+    }
+
+    fn visit_attribute(&mut self, attribute: &Attribute) {
+        if self.target != Target::Lua54 {
+            self.unsupported(attribute, "a variable attribute");
+        }
+    }
+
+    fn visit_compound_assignment(&mut self, assignment: &CompoundAssignment) {
+        self.luau_only(assignment, "a compound assignment");
+    }
+
+    fn visit_luau_attribute(&mut self, attribute: &LuauAttribute) {
+        self.luau_only(attribute, "a function attribute");
+    }
+
+    fn visit_type_specifier(&mut self, type_specifier: &TypeSpecifier) {
+        self.luau_only(type_specifier, "a type annotation");
+    }
+
+    fn visit_type_assertion(&mut self, type_assertion: &TypeAssertion) {
+        self.luau_only(type_assertion, "a type assertion");
+    }
+
+    fn visit_type_declaration(&mut self, declaration: &TypeDeclaration) {
+        self.luau_only(declaration, "a type declaration");
+    }
+
+    fn visit_exported_type_declaration(&mut self, declaration: &ExportedTypeDeclaration) {
+        self.luau_only(declaration, "a type declaration");
+    }
+
+    fn visit_type_function(&mut self, function: &TypeFunction) {
+        self.luau_only(function, "a type function");
+    }
+
+    fn visit_exported_type_function(&mut self, function: &ExportedTypeFunction) {
+        self.luau_only(function, "a type function");
+    }
+
+    fn visit_generic_declaration(&mut self, generics: &GenericDeclaration) {
+        self.luau_only(generics, "a generic declaration");
+    }
+
+    fn visit_type_instantiation(&mut self, instantiation: &TypeInstantiation) {
+        self.luau_only(instantiation, "an explicit type instantiation");
+    }
+}
+
+/// Fails on the first construct of `block` which `target` can't run
+pub fn check(block: &Block, target: Target) -> Result<(), Error> {
+    let mut checker = Checker {
+        target,
+        unsupported: None,
+    };
+    block.visit(&mut checker);
+    match checker.unsupported {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checked(target: Target, source: &str) -> Result<(), Error> {
+        let ast = full_moon::parse_fallible(source, Target::Lua51.lua_version())
+            .into_result()
+            .unwrap();
+        check(ast.nodes(), target)
+    }
+
+    #[test]
+    fn rejects_goto_as_a_name_where_it_is_a_keyword() {
+        let sources = [
+            "goto = 1 print(goto)",
+            "local goto = 1",
+            "t.x = function(goto) end",
+            "t.goto = 1",
+        ];
+        for source in sources {
+            for target in [Target::Lua51, Target::Luau] {
+                assert!(checked(target, source).is_ok(), "{source} on {target:?}");
+            }
+            for target in [Target::Lua52, Target::Lua53, Target::Lua54, Target::LuaJit] {
+                let result = checked(target, source);
+                assert!(
+                    matches!(&result, Err(Error::Unsupported { message, .. })
+                        if message.starts_with("the name `goto` can't run on")),
+                    "{source} on {target:?}: {result:?}"
+                );
+            }
+        }
+        assert!(checked(Target::Lua54, "t['goto'] = 1").is_ok());
+    }
+}
@@ -0,0 +1,103 @@
+use full_moon::{
+    self,
+    ast::{
+        span::ContainedSpan, Block, Expression, FunctionBody, FunctionCall, GenericFor,
+        LocalAssignment, MethodCall, NumericFor, Stmt, Suffix, VarExpression,
+    },
+    tokenizer::Symbol,
+    visitors::VisitorMut,
+};
+
+use crate::minifier::{merge::is_multiple_values, utils::symbol};
+
+/// Removes all Luau type syntax: annotations, generics, type declarations, type functions,
+/// `::` assertions and explicit type instantiations
+pub struct TypeStripper;
+
+fn without_type_instantiations(suffixes: impl Iterator<Item = Suffix>) -> Vec<Suffix> {
+    suffixes
+        .filter(|suffix| !matches!(suffix, Suffix::TypeInstantiation(_)))
+        .collect()
+}
+
+impl VisitorMut for TypeStripper {
+    fn visit_block(&mut self, block: Block) -> Block {
+        let stmts = block
+            .stmts_with_semicolon()
+            .filter(|(stmt, _)| {
+                !matches!(
+                    stmt,
+                    Stmt::TypeDeclaration(_)
+                        | Stmt::ExportedTypeDeclaration(_)
+                        | Stmt::TypeFunction(_)
+                        | Stmt::ExportedTypeFunction(_)
+                )
+            })
+            .cloned()
+            .collect();
+        block.with_stmts(stmts)
+    }
+
+    fn visit_local_assignment(&mut self, local_assignment: LocalAssignment) -> LocalAssignment {
+        let type_specifiers = vec![None; local_assignment.names().len()];
+        local_assignment.with_type_specifiers(type_specifiers)
+    }
+
+    fn visit_function_body(&mut self, body: FunctionBody) -> FunctionBody {
+        let type_specifiers = vec![None; body.parameters().len()];
+        body.with_generics(None)
+            .with_type_specifiers(type_specifiers)
+            .with_return_type(None)
+    }
+
+    fn visit_generic_for(&mut self, generic_for: GenericFor) -> GenericFor {
+        let type_specifiers = vec![None; generic_for.names().len()];
+        generic_for.with_type_specifiers(type_specifiers)
+    }
+
+    fn visit_numeric_for(&mut self, numeric_for: NumericFor) -> NumericFor {
+        numeric_for.with_type_specifier(None)
+    }
+
+    fn visit_expression_end(&mut self, expression: Expression) -> Expression {
+        match expression {
+            // An assertion truncates a call or `...` to its first value, like parentheses do
+            Expression::TypeAssertion { expression, .. } if is_multiple_values(&expression) => {
+                Expression::Parentheses {
+                    contained: ContainedSpan::new(
+                        symbol(Symbol::LeftParen),
+                        symbol(Symbol::RightParen),
+                    ),
+                    expression,
+                }
+            }
+            Expression::TypeAssertion { expression, .. } => *expression,
+            // `((f() :: T))` would otherwise end up with two pairs of parentheses
+            Expression::Parentheses {
+                contained,
+                expression,
+            } => match *expression {
+                inner @ Expression::Parentheses { .. } => inner,
+                expression => Expression::Parentheses {
+                    contained,
+                    expression: Box::new(expression),
+                },
+            },
+            _ => expression,
+        }
+    }
+
+    fn visit_function_call_end(&mut self, call: FunctionCall) -> FunctionCall {
+        let suffixes = without_type_instantiations(call.suffixes().cloned());
+        call.with_suffixes(suffixes)
+    }
+
+    fn visit_var_expression_end(&mut self, var: VarExpression) -> VarExpression {
+        let suffixes = without_type_instantiations(var.suffixes().cloned());
+        var.with_suffixes(suffixes)
+    }
+
+    fn visit_method_call(&mut self, method_call: MethodCall) -> MethodCall {
+        method_call.with_type_instanation(None)
+    }
+}
@@ -1,26 +1,42 @@
-use std::collections::HashMap;
+use full_moon::tokenizer::{Symbol, Token, TokenReference, TokenType};
 
-/// Generates the next shortest variable name in sequence (a, b, c, ..., aa, ab, ac, ...)
-fn generate_next_name(current_index: usize) -> String {
+/// Characters an identifier may start with
+const NAME_START: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+/// Characters allowed after the first character of an identifier
+const NAME_CONTINUE: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789_";
+
+/// Keywords of every supported dialect, plus Luau's contextual keywords which are
+/// legal identifiers but confusing ones
+const RESERVED: &[&str] = &[
+    "and", "break", "continue", "do", "else", "elseif", "end", "export", "false", "for",
+    "function", "goto", "if", "in", "local", "nil", "not", "or", "repeat", "return", "then",
+    "true", "type", "typeof", "until", "while",
+];
+
+/// A token without trivia or a position, for syntax the minifier adds
+pub fn token(token_type: TokenType) -> TokenReference {
+    TokenReference::new(Vec::new(), Token::new(token_type), Vec::new())
+}
+
+pub fn symbol(symbol: Symbol) -> TokenReference {
+    token(TokenType::Symbol { symbol })
+}
+
+/// Generates the next shortest variable name in sequence (a, b, ..., Z, aa, ba, ..., a0, ...)
+pub fn generate_next_name(current_index: usize) -> String {
     let mut name = String::new();
     let mut n = current_index;
-
-    loop {
-        let remainder = n % 26;
-        name.insert(0, ((remainder as u8) + b'a') as char);
-        n /= 26;
-        if n == 0 {
-            break;
-        }
+    name.push(NAME_START[n % NAME_START.len()] as char);
+    n /= NAME_START.len();
+    while n > 0 {
         n -= 1; // Adjust for 0-indexing in the next iteration
+        name.push(NAME_CONTINUE[n % NAME_CONTINUE.len()] as char);
+        n /= NAME_CONTINUE.len();
     }
-
     name
 }
 
-/// Mutates the provided HashMap by mapping each unique key to a unique shortest variable name
-/// The keys are mapped to names like a, b, c, ..., aa, ab, ac, ...
-pub fn generate_shortest_names(map: &mut HashMap<String, String>, key: String) {
-    let index = map.len();
-    map.insert(key, generate_next_name(index));
+/// Whether a name can't be used as an identifier
+pub fn is_reserved(name: &str) -> bool {
+    RESERVED.contains(&name)
 }
@@ -0,0 +1,1388 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    iter, mem,
+};
+
+use full_moon::{
+    self,
+    ast::{
+        punctuated::Punctuated, Assignment, Ast, BinOp, Block, Call, Expression, Field,
+        FunctionArgs, FunctionBody, FunctionCall, If, Index, LastStmt, LocalAssignment, Parameter,
+        Prefix, Stmt, Suffix, TableConstructor, UnOp, Var,
+    },
+    node::Node,
+    tokenizer::{TokenReference, TokenType},
+};
+
+use crate::{
+    minifier::{
+        emitter::Emitter,
+        literals::{decode_string, escape_non_ascii, parse_number},
+        reference::{Constant, Reference},
+        scope::ScopeTree,
+    },
+    Error, Options, Position,
+};
+
+/// Longest rendering of a node we quote in an error
+const MAX_QUOTE: usize = 120;
+
+/// Re-parses `code` and checks that it does what `source` does, statement by statement.
+///
+/// Nothing the minifier computed is trusted: identifiers are resolved through the scopes of
+/// each side, constants are evaluated by a separate reference evaluator, dead code is worked
+/// out again from the source, and statements merged into one are checked against the
+/// semantics of a single assignment, where every value is evaluated before anything is
+/// assigned.
+pub fn verify(
+    source: &str,
+    ast: &Ast,
+    code: &str,
+    renames: &HashMap<usize, String>,
+    options: &Options,
+) -> Result<(), Error> {
+    let output = full_moon::parse_fallible(code, options.target.lua_version())
+        .into_result()
+        .map_err(|errors| Error::Verify {
+            position: Position::from_offset(source, 0),
+            expected: String::from("code which parses"),
+            found: errors
+                .iter()
+                .map(|error| error.to_string())
+                .collect::<Vec<String>>()
+                .join(", "),
+        })?;
+    let mut comparison = Comparison {
+        reference: Reference {
+            target: options.target,
+        },
+        remove_dead_code: options.remove_dead_code,
+        renames,
+        source: Side::new(source, ast.nodes())?,
+        output: Side::new(code, output.nodes())?,
+        pairs: HashMap::new(),
+        reverse: HashMap::new(),
+    };
+    comparison
+        .block(ast.nodes(), output.nodes(), 0)
+        .map_err(|mismatch| Error::Verify {
+            position: Position::from_offset(source, mismatch.offset),
+            expected: quote(mismatch.expected),
+            found: quote(mismatch.found),
+        })
+}
+
+fn quote(mut text: String) -> String {
+    if text.chars().count() > MAX_QUOTE {
+        text = text.chars().take(MAX_QUOTE).collect();
+        text.push_str("...");
+    }
+    text
+}
+
+/// The innermost node which differs between the source and the minified code
+struct Mismatch {
+    /// Byte offset of the node in the source
+    offset: usize,
+    expected: String,
+    found: String,
+}
+
+type Compared = Result<(), Mismatch>;
+
+/// Where a node starts
+fn start(node: &impl Node) -> Option<usize> {
+    Node::start_position(node).map(|position| position.bytes())
+}
+
+fn position(token: &TokenReference) -> usize {
+    token.token().start_position().bytes()
+}
+
+fn render(write: impl FnOnce(&mut Emitter)) -> String {
+    let renames = HashMap::new();
+    let mut emitter = Emitter::new(&renames);
+    write(&mut emitter);
+    emitter.finish()
+}
+
+fn render_stmt(stmt: &Stmt) -> String {
+    render(|emitter| emitter.stmt(stmt))
+}
+
+fn render_expression(expression: &Expression) -> String {
+    render(|emitter| emitter.expression(expression))
+}
+
+/// A node as it is written, on one line
+fn render_node(node: &impl Display) -> String {
+    node.to_string()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+impl Mismatch {
+    fn nodes(expected: &(impl Node + Display), found: &(impl Node + Display)) -> Self {
+        Mismatch {
+            offset: start(expected).unwrap_or_default(),
+            expected: render_node(expected),
+            found: render_node(found),
+        }
+    }
+
+    fn stmts(expected: &Stmt, found: &Stmt) -> Self {
+        Mismatch {
+            offset: start(expected).unwrap_or_default(),
+            expected: render_stmt(expected),
+            found: render_stmt(found),
+        }
+    }
+
+    fn expressions(expected: &Expression, found: &Expression) -> Self {
+        Mismatch {
+            offset: start(expected).unwrap_or_default(),
+            expected: render_expression(expected),
+            found: render_expression(found),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Name {
+    Local(usize),
+    Global(usize),
+}
+
+/// What the identifiers of the source or of the minified code refer to
+struct Side<'a> {
+    code: &'a str,
+    scopes: ScopeTree,
+    /// The local or global named by each identifier, keyed by its byte offset
+    names: HashMap<usize, Name>,
+}
+
+impl<'a> Side<'a> {
+    fn new(code: &'a str, block: &Block) -> Result<Self, Error> {
+        let scopes = ScopeTree::build(block)?;
+        let mut names = HashMap::new();
+        for (id, binding) in scopes.bindings.iter().enumerate() {
+            names.extend(
+                binding
+                    .positions
+                    .iter()
+                    .map(|&offset| (offset, Name::Local(id))),
+            );
+        }
+        for (id, global) in scopes.globals.iter().enumerate() {
+            names.extend(
+                global
+                    .positions
+                    .iter()
+                    .map(|&offset| (offset, Name::Global(id))),
+            );
+        }
+        Ok(Side {
+            code,
+            scopes,
+            names,
+        })
+    }
+
+    fn name(&self, token: &TokenReference) -> Option<Name> {
+        self.names.get(&position(token)).copied()
+    }
+
+    /// An identifier along with what it refers to
+    fn describe(&self, token: &TokenReference) -> String {
+        let text = token.token().to_string();
+        match self.name(token) {
+            Some(Name::Local(id)) => match self.scopes.bindings[id].positions.first() {
+                Some(&declaration) => {
+                    let declaration = Position::from_offset(self.code, declaration);
+                    format!(
+                        "{} (the local declared at {}:{})",
+                        text, declaration.line, declaration.column
+                    )
+                }
+                None => format!("{} (a local)", text),
+            },
+            Some(Name::Global(_)) => format!("{} (a global)", text),
+            None => text,
+        }
+    }
+}
+
+/// A statement of the source, in the form the minified code holds it once dead code is gone
+struct Unit<'a> {
+    /// The statement as it is written in the source
+    stmt: &'a Stmt,
+    kind: Kind<'a>,
+}
+
+enum Kind<'a> {
+    Stmt,
+    /// An `if` reduced to the branches which can run
+    If {
+        branches: Vec<(&'a Expression, &'a Block)>,
+        else_block: Option<&'a Block>,
+    },
+    /// A branch which always runs, in a `do` block to keep its locals and labels to itself
+    Do(&'a Block),
+}
+
+enum Simplified<'a> {
+    Keep(Unit<'a>),
+    Remove,
+    /// The statements of a branch which always runs, in place of the `if`
+    Inline(&'a Block),
+}
+
+struct Frame<'a> {
+    stmts: Vec<&'a Stmt>,
+    next: usize,
+    /// The `return` or `break` of an inlined branch, which replaces the one of the block
+    last_stmt: Option<&'a LastStmt>,
+}
+
+/// Walks the statements of a source block which the minified block should still hold, with the
+/// branches which always run inlined and without the statements which can never run.
+/// Conditions are evaluated with the reference evaluator.
+struct Live<'a> {
+    reference: Reference,
+    remove_dead_code: bool,
+    /// A `goto` can jump past the statement which leaves a block with labels
+    labels: bool,
+    /// The block and the inlined branches being walked, innermost last
+    frames: Vec<Frame<'a>>,
+    last_stmt: Option<&'a LastStmt>,
+    /// Set once a statement always leaves the block
+    dead: bool,
+}
+
+impl<'a> Live<'a> {
+    fn new(reference: Reference, remove_dead_code: bool, block: &'a Block) -> Self {
+        Live {
+            reference,
+            remove_dead_code,
+            labels: block.stmts().any(|stmt| matches!(stmt, Stmt::Label(_))),
+            frames: vec![Frame {
+                stmts: block.stmts().collect(),
+                next: 0,
+                last_stmt: None,
+            }],
+            last_stmt: block.last_stmt(),
+            dead: false,
+        }
+    }
+
+    /// The next statement. `hint` is the statement of the minified code expected to match it:
+    /// a conditional the reference evaluator knows the value of may still be kept as it is,
+    /// since the minifier doesn't have to know it too.
+    fn next(&mut self, hint: Option<&Stmt>) -> Option<Unit<'a>> {
+        loop {
+            if self.dead {
+                return None;
+            }
+            let frame = self.frames.last_mut()?;
+            let Some(&stmt) = frame.stmts.get(frame.next) else {
+                if self.frames.len() == 1 {
+                    return None;
+                }
+                if let Some(last_stmt) = self.frames.pop().and_then(|frame| frame.last_stmt) {
+                    self.last_stmt = Some(last_stmt);
+                    self.dead = true;
+                }
+                continue;
+            };
+            frame.next += 1;
+            let unit = match self.simplify(stmt, hint) {
+                Simplified::Keep(unit) => unit,
+                Simplified::Remove => continue,
+                Simplified::Inline(block) if self.labels || self.declares_names(block) => Unit {
+                    stmt,
+                    kind: Kind::Do(block),
+                },
+                Simplified::Inline(block) => {
+                    self.frames.push(Frame {
+                        stmts: block.stmts().collect(),
+                        next: 0,
+                        last_stmt: block.last_stmt(),
+                    });
+                    continue;
+                }
+            };
+            if self.remove_dead_code && !self.labels && self.exits(&unit) {
+                self.dead = true;
+                self.last_stmt = None;
+            }
+            return Some(unit);
+        }
+    }
+
+    fn condition(&self, expression: &Expression) -> Option<bool> {
+        self.reference
+            .evaluate(expression)
+            .map(|value| value.is_truthy())
+    }
+
+    fn simplify(&self, stmt: &'a Stmt, hint: Option<&Stmt>) -> Simplified<'a> {
+        let keep = |kind| Simplified::Keep(Unit { stmt, kind });
+        match stmt {
+            Stmt::If(x) => {
+                let mut branches = vec![(x.condition(), x.block())];
+                branches.extend(
+                    x.else_if()
+                        .into_iter()
+                        .flatten()
+                        .map(|else_if| (else_if.condition(), else_if.block())),
+                );
+                let kept_as_is = match hint {
+                    Some(Stmt::If(y)) => {
+                        y.else_if().map_or(0, Vec::len) + 1 == branches.len()
+                            && y.else_block().is_some() == x.else_block().is_some()
+                            && self.condition(y.condition()).is_some()
+                    }
+                    _ => false,
+                };
+                if !self.remove_dead_code
+                    || kept_as_is
+                    || branches
+                        .iter()
+                        .all(|(condition, _)| self.condition(condition).is_none())
+                {
+                    return keep(Kind::If {
+                        branches,
+                        else_block: x.else_block(),
+                    });
+                }
+                let mut kept = Vec::new();
+                let mut else_block = x.else_block();
+                for (condition, block) in branches {
+                    match self.condition(condition) {
+                        Some(false) => {}
+                        // Later branches can never run
+                        Some(true) => {
+                            else_block = Some(block);
+                            break;
+                        }
+                        None => kept.push((condition, block)),
+                    }
+                }
+                match else_block {
+                    _ if !kept.is_empty() => keep(Kind::If {
+                        branches: kept,
+                        else_block,
+                    }),
+                    Some(block) => Simplified::Inline(block),
+                    None => Simplified::Remove,
+                }
+            }
+            Stmt::While(x)
+                if self.remove_dead_code && self.condition(x.condition()) == Some(false) =>
+            {
+                match hint {
+                    Some(Stmt::While(y)) if self.condition(y.condition()).is_some() => {
+                        keep(Kind::Stmt)
+                    }
+                    _ => Simplified::Remove,
+                }
+            }
+            _ => keep(Kind::Stmt),
+        }
+    }
+
+    /// Whether the statements after `unit` can never run
+    fn exits(&self, unit: &Unit<'a>) -> bool {
+        match &unit.kind {
+            Kind::Stmt => match unit.stmt {
+                Stmt::Do(x) => self.exits_block(x.block()),
+                _ => false,
+            },
+            Kind::If {
+                branches,
+                else_block,
+            } => {
+                else_block.is_some_and(|block| self.exits_block(block))
+                    && branches.iter().all(|(_, block)| self.exits_block(block))
+            }
+            Kind::Do(block) => self.exits_block(block),
+        }
+    }
+
+    fn exits_block(&self, block: &'a Block) -> bool {
+        let mut live = Live::new(self.reference, self.remove_dead_code, block);
+        while live.next(None).is_some() {}
+        live.dead || live.last_stmt.is_some()
+    }
+
+    /// Whether the live statements of a block declare locals, types or labels
+    fn declares_names(&self, block: &'a Block) -> bool {
+        let mut live = Live::new(self.reference, self.remove_dead_code, block);
+        iter::from_fn(|| live.next(None)).any(|unit| {
+            matches!(unit.kind, Kind::Stmt)
+                && matches!(
+                    unit.stmt,
+                    Stmt::LocalAssignment(_)
+                        | Stmt::LocalFunction(_)
+                        | Stmt::TypeDeclaration(_)
+                        | Stmt::ExportedTypeDeclaration(_)
+                        | Stmt::TypeFunction(_)
+                        | Stmt::ExportedTypeFunction(_)
+                        | Stmt::Label(_)
+                )
+        })
+    }
+}
+
+/// The value a name of a statement gets, once the statements merged into it are lined up
+#[derive(Clone, Copy)]
+enum Value<'a> {
+    Expression(&'a Expression),
+    Nil,
+}
+
+/// Whether an expression gives all its values at the end of a list
+fn multiple_values(expression: &Expression) -> bool {
+    match expression {
+        Expression::FunctionCall(_) => true,
+        Expression::Symbol(token) => token.token().to_string() == "...",
+        _ => false,
+    }
+}
+
+/// Lines up the values of statements merged into one with their names. `None` if a statement
+/// other than the last one has values to spare, which would go to the names after it.
+fn line_up<'a>(members: &[(usize, &'a Punctuated<Expression>)]) -> Option<Vec<Value<'a>>> {
+    let mut values = Vec::new();
+    for (i, &(count, expressions)) in members.iter().enumerate() {
+        let last = i + 1 == members.len();
+        let spread =
+            expressions.len() < count && expressions.iter().last().is_some_and(multiple_values);
+        if !last && (expressions.len() > count || spread) {
+            return None;
+        }
+        values.extend(expressions.iter().map(Value::Expression));
+        if !spread {
+            values.extend(iter::repeat_n(
+                Value::Nil,
+                count.saturating_sub(expressions.len()),
+            ));
+        }
+    }
+    Some(values)
+}
+
+/// How many names a declaration or assignment gives values to
+fn targets(stmt: &Stmt) -> usize {
+    match stmt {
+        Stmt::LocalAssignment(x) => x.names().len(),
+        Stmt::Assignment(x) => x.variables().len(),
+        _ => 0,
+    }
+}
+
+struct Comparison<'a> {
+    reference: Reference,
+    remove_dead_code: bool,
+    /// New names of the mangled globals, keyed by the byte offset of each reference
+    renames: &'a HashMap<usize, String>,
+    source: Side<'a>,
+    output: Side<'a>,
+    /// Locals of the source paired with the locals of the minified code standing for them
+    pairs: HashMap<usize, usize>,
+    reverse: HashMap<usize, usize>,
+}
+
+impl Comparison<'_> {
+    /// `fallback` is where the block starts, for when the source has fewer statements
+    fn block(&mut self, expected: &Block, found: &Block, fallback: usize) -> Compared {
+        let found_stmts: Vec<&Stmt> = found.stmts().collect();
+        let mut live = Live::new(self.reference, self.remove_dead_code, expected);
+        let mut offset = fallback;
+        let mut pending = None;
+        let mut j = 0;
+        while let Some(unit) = pending
+            .take()
+            .or_else(|| live.next(found_stmts.get(j).copied()))
+        {
+            offset = start(unit.stmt).unwrap_or(offset);
+            let Some(&found) = found_stmts.get(j) else {
+                return Err(Mismatch {
+                    offset,
+                    expected: render_stmt(unit.stmt),
+                    found: String::from("nothing"),
+                });
+            };
+            let merged = matches!(unit.kind, Kind::Stmt)
+                && matches!(
+                    (unit.stmt, found),
+                    (Stmt::LocalAssignment(_), Stmt::LocalAssignment(_))
+                        | (Stmt::Assignment(_), Stmt::Assignment(_))
+                );
+            if merged {
+                // Gather the statements the minifier may have merged into this one
+                let mut group = vec![unit.stmt];
+                let mut count = targets(unit.stmt);
+                while count < targets(found) {
+                    match live.next(found_stmts.get(j + 1).copied()) {
+                        Some(next)
+                            if matches!(next.kind, Kind::Stmt)
+                                && mem::discriminant(next.stmt) == mem::discriminant(found) =>
+                        {
+                            count += targets(next.stmt);
+                            group.push(next.stmt);
+                        }
+                        next => {
+                            pending = next;
+                            break;
+                        }
+                    }
+                }
+                self.group(&group, found)?;
+            } else {
+                self.unit(unit, found)?;
+            }
+            j += 1;
+        }
+        if let Some(&found) = found_stmts.get(j) {
+            return Err(Mismatch {
+                offset,
+                expected: String::from("nothing"),
+                found: render_stmt(found),
+            });
+        }
+        self.last_stmt(live.last_stmt, found.last_stmt(), offset)
+    }
+
+    fn last_stmt(
+        &mut self,
+        expected: Option<&LastStmt>,
+        found: Option<&LastStmt>,
+        fallback: usize,
+    ) -> Compared {
+        let same = match (expected, found) {
+            (None, None) => return Ok(()),
+            (Some(LastStmt::Return(x)), Some(LastStmt::Return(y))) => {
+                x.returns().len() == y.returns().len() && {
+                    for (expected, found) in x.returns().iter().zip(y.returns()) {
+                        self.expression(expected, found)?;
+                    }
+                    true
+                }
+            }
+            (Some(LastStmt::Break(_)), Some(LastStmt::Break(_)))
+            | (Some(LastStmt::Continue(_)), Some(LastStmt::Continue(_))) => true,
+            _ => false,
+        };
+        if same {
+            return Ok(());
+        }
+        let render = |last_stmt: Option<&LastStmt>| match last_stmt {
+            Some(last_stmt) => render(|emitter| emitter.last_stmt(last_stmt)),
+            None => String::from("nothing"),
+        };
+        Err(Mismatch {
+            offset: expected.and_then(start).unwrap_or(fallback),
+            expected: render(expected),
+            found: render(found),
+        })
+    }
+
+    fn unit(&mut self, unit: Unit, found: &Stmt) -> Compared {
+        let offset = start(unit.stmt).unwrap_or_default();
+        match (unit.kind, found) {
+            (Kind::Stmt, found) => self.stmt(unit.stmt, found),
+            (Kind::Do(block), Stmt::Do(y)) => self.block(block, y.block(), offset),
+            (
+                Kind::If {
+                    branches,
+                    else_block,
+                },
+                Stmt::If(y),
+            ) => self.conditional(unit.stmt, &branches, else_block, y),
+            _ => Err(Mismatch::stmts(unit.stmt, found)),
+        }
+    }
+
+    fn conditional(
+        &mut self,
+        stmt: &Stmt,
+        branches: &[(&Expression, &Block)],
+        else_block: Option<&Block>,
+        found: &If,
+    ) -> Compared {
+        let mut found_branches = vec![(found.condition(), found.block())];
+        found_branches.extend(
+            found
+                .else_if()
+                .into_iter()
+                .flatten()
+                .map(|else_if| (else_if.condition(), else_if.block())),
+        );
+        if branches.len() != found_branches.len()
+            || else_block.is_some() != found.else_block().is_some()
+        {
+            return Err(Mismatch {
+                offset: start(stmt).unwrap_or_default(),
+                expected: render_stmt(stmt),
+                found: render(|emitter| emitter.stmt(&Stmt::If(found.clone()))),
+            });
+        }
+        for (&(condition, block), (found_condition, found_block)) in
+            branches.iter().zip(found_branches)
+        {
+            self.expression(condition, found_condition)?;
+            self.block(block, found_block, start(condition).unwrap_or_default())?;
+        }
+        if let (Some(block), Some(found_block)) = (else_block, found.else_block()) {
+            self.block(block, found_block, start(stmt).unwrap_or_default())?;
+        }
+        Ok(())
+    }
+
+    fn stmt(&mut self, expected: &Stmt, found: &Stmt) -> Compared {
+        let offset = start(expected).unwrap_or_default();
+        match (expected, found) {
+            (Stmt::LocalAssignment(_), Stmt::LocalAssignment(_))
+            | (Stmt::Assignment(_), Stmt::Assignment(_)) => self.group(&[expected], found),
+            (Stmt::CompoundAssignment(x), Stmt::CompoundAssignment(y)) => {
+                self.var(x.lhs(), y.lhs())?;
+                self.tokens(x.compound_operator(), y.compound_operator())?;
+                self.expression(x.rhs(), y.rhs())
+            }
+            (Stmt::Do(x), Stmt::Do(y)) => self.block(x.block(), y.block(), offset),
+            (Stmt::FunctionCall(x), Stmt::FunctionCall(y)) => self.function_call(x, y),
+            (Stmt::FunctionDeclaration(x), Stmt::FunctionDeclaration(y)) => {
+                self.attributes(x.attributes(), y.attributes(), offset)?;
+                let (names, found_names) = (x.name().names(), y.name().names());
+                if names.len() != found_names.len() {
+                    return Err(Mismatch::nodes(x.name(), y.name()));
+                }
+                for (i, (name, found_name)) in names.iter().zip(found_names).enumerate() {
+                    match i {
+                        0 => self.name(name, found_name)?,
+                        _ => self.tokens(name, found_name)?,
+                    }
+                }
+                self.optional(x.name().method_name(), y.name().method_name(), offset)?;
+                self.body(x.body(), y.body())
+            }
+            (Stmt::GenericFor(x), Stmt::GenericFor(y)) => {
+                self.list(x.expressions(), y.expressions(), expected, found)?;
+                if x.names().len() != y.names().len() {
+                    return Err(Mismatch::stmts(expected, found));
+                }
+                for ((name, type_specifier), (found_name, found_type_specifier)) in x
+                    .names()
+                    .iter()
+                    .zip(x.type_specifiers())
+                    .zip(y.names().iter().zip(y.type_specifiers()))
+                {
+                    self.name(name, found_name)?;
+                    self.optional(type_specifier, found_type_specifier, position(name))?;
+                }
+                self.block(x.block(), y.block(), offset)
+            }
+            (Stmt::LocalFunction(x), Stmt::LocalFunction(y)) => {
+                self.attributes(x.attributes(), y.attributes(), offset)?;
+                self.name(x.name(), y.name())?;
+                self.body(x.body(), y.body())
+            }
+            (Stmt::NumericFor(x), Stmt::NumericFor(y)) => {
+                self.expression(x.start(), y.start())?;
+                self.expression(x.end(), y.end())?;
+                match (x.step(), y.step()) {
+                    (Some(step), Some(found_step)) => self.expression(step, found_step)?,
+                    (None, None) => {}
+                    _ => return Err(Mismatch::stmts(expected, found)),
+                }
+                self.name(x.index_variable(), y.index_variable())?;
+                self.optional(x.type_specifier(), y.type_specifier(), offset)?;
+                self.block(x.block(), y.block(), offset)
+            }
+            (Stmt::Repeat(x), Stmt::Repeat(y)) => {
+                self.block(x.block(), y.block(), offset)?;
+                self.expression(x.until(), y.until())
+            }
+            (Stmt::While(x), Stmt::While(y)) => {
+                self.expression(x.condition(), y.condition())?;
+                self.block(x.block(), y.block(), offset)
+            }
+            (Stmt::If(x), Stmt::If(y)) => {
+                let mut branches = vec![(x.condition(), x.block())];
+                branches.extend(
+                    x.else_if()
+                        .into_iter()
+                        .flatten()
+                        .map(|else_if| (else_if.condition(), else_if.block())),
+                );
+                self.conditional(expected, &branches, x.else_block(), y)
+            }
+            // Labels, `goto` and type declarations are compared token by token
+            (expected, found) if mem::discriminant(expected) == mem::discriminant(found) => self
+                .tokens(expected, found)
+                .map_err(|_| Mismatch::stmts(expected, found)),
+            _ => Err(Mismatch::stmts(expected, found)),
+        }
+    }
+
+    /// Compares a declaration or assignment with the statements of the source merged into it.
+    /// Values are evaluated before any name is assigned, so statements after the first one
+    /// can't read what the ones before them assign.
+    fn group(&mut self, group: &[&Stmt], found: &Stmt) -> Compared {
+        let mismatch = || Mismatch {
+            offset: start(group[0]).unwrap_or_default(),
+            expected: group
+                .iter()
+                .map(|stmt| render_stmt(stmt))
+                .collect::<Vec<String>>()
+                .join(" "),
+            found: render_stmt(found),
+        };
+        match found {
+            Stmt::LocalAssignment(y) => {
+                let members: Vec<&LocalAssignment> = group
+                    .iter()
+                    .filter_map(|stmt| match stmt {
+                        Stmt::LocalAssignment(x) => Some(x),
+                        _ => None,
+                    })
+                    .collect();
+                // A statement can declare only one `<close>` variable
+                if members.len() > 1
+                    && members
+                        .iter()
+                        .any(|x| x.attributes().any(|attribute| attribute.is_some()))
+                {
+                    return Err(mismatch());
+                }
+                let names = |x: &LocalAssignment| {
+                    let names: Vec<_> = x
+                        .names()
+                        .iter()
+                        .zip(x.attributes().chain(iter::repeat(None)))
+                        .zip(x.type_specifiers().chain(iter::repeat(None)))
+                        .map(|((name, attribute), type_specifier)| {
+                            (name.clone(), attribute.cloned(), type_specifier.cloned())
+                        })
+                        .collect();
+                    names
+                };
+                let expected_names: Vec<_> = members.iter().flat_map(|x| names(x)).collect();
+                let found_names = names(y);
+                if expected_names.len() != found_names.len() {
+                    return Err(mismatch());
+                }
+                for (
+                    (name, attribute, type_specifier),
+                    (found_name, found_attribute, found_type),
+                ) in expected_names.iter().zip(&found_names)
+                {
+                    self.name(name, found_name)?;
+                    self.optional(attribute.as_ref(), found_attribute.as_ref(), position(name))?;
+                    self.optional(type_specifier.as_ref(), found_type.as_ref(), position(name))?;
+                }
+                let members: Vec<_> = members
+                    .iter()
+                    .map(|x| (x.names().len(), x.expressions()))
+                    .collect();
+                let values = line_up(&members).ok_or_else(mismatch)?;
+                self.values(&values, y.expressions(), &mismatch)
+            }
+            Stmt::Assignment(y) => {
+                let members: Vec<&Assignment> = group
+                    .iter()
+                    .filter_map(|stmt| match stmt {
+                        Stmt::Assignment(x) => Some(x),
+                        _ => None,
+                    })
+                    .collect();
+                if members.len() > 1 && !self.parallel(&members) {
+                    return Err(mismatch());
+                }
+                let variables: Vec<&Var> = members.iter().flat_map(|x| x.variables()).collect();
+                if variables.len() != y.variables().len() {
+                    return Err(mismatch());
+                }
+                for (var, found_var) in variables.into_iter().zip(y.variables()) {
+                    self.var(var, found_var)?;
+                }
+                let members: Vec<_> = members
+                    .iter()
+                    .map(|x| (x.variables().len(), x.expressions()))
+                    .collect();
+                let values = line_up(&members).ok_or_else(mismatch)?;
+                self.values(&values, y.expressions(), &mismatch)
+            }
+            _ => Err(mismatch()),
+        }
+    }
+
+    /// Whether assignments give the same result as one assignment of all their values, which
+    /// holds when they only write to locals and the later ones neither read the locals written
+    /// before them nor run code which could
+    fn parallel(&self, members: &[&Assignment]) -> bool {
+        let mut written = HashSet::new();
+        for (i, x) in members.iter().enumerate() {
+            if i > 0
+                && x.expressions()
+                    .iter()
+                    .any(|expression| !self.is_pure(expression) || self.reads(expression, &written))
+            {
+                return false;
+            }
+            let mut targets = HashSet::new();
+            for var in x.variables() {
+                let Var::Name(name) = var else {
+                    return false;
+                };
+                match self.source.name(name) {
+                    Some(Name::Local(binding)) if !written.contains(&binding) => {
+                        targets.insert(binding);
+                    }
+                    _ => return false,
+                }
+            }
+            written.extend(targets);
+        }
+        true
+    }
+
+    /// Whether a source expression, including any function in it, names one of `bindings`
+    fn reads(&self, expression: &Expression, bindings: &HashSet<usize>) -> bool {
+        expression.tokens().any(|token| {
+            matches!(token.token_type(), TokenType::Identifier { .. })
+                && matches!(
+                    self.source.name(token),
+                    Some(Name::Local(binding)) if bindings.contains(&binding)
+                )
+        })
+    }
+
+    /// Whether evaluating a source expression can't run any code
+    fn is_pure(&self, expression: &Expression) -> bool {
+        match expression {
+            Expression::Number(_)
+            | Expression::String(_)
+            | Expression::Symbol(_)
+            | Expression::Function(_) => true,
+            Expression::TableConstructor(table) => table.fields().iter().all(|field| match field {
+                Field::ExpressionKey { key, value, .. } => self.is_pure(key) && self.is_pure(value),
+                Field::NameKey { value, .. } | Field::NoKey(value) => self.is_pure(value),
+                _ => false,
+            }),
+            Expression::Parentheses { expression, .. }
+            | Expression::UnaryOperator {
+                unop: UnOp::Not(_),
+                expression,
+            }
+            | Expression::TypeAssertion { expression, .. } => self.is_pure(expression),
+            Expression::BinaryOperator {
+                lhs,
+                binop: BinOp::And(_) | BinOp::Or(_),
+                rhs,
+            } => self.is_pure(lhs) && self.is_pure(rhs),
+            Expression::Var(Var::Name(name)) => {
+                matches!(self.source.name(name), Some(Name::Local(_)))
+            }
+            expression => self.reference.evaluate(expression).is_some(),
+        }
+    }
+
+    fn is_nil(&self, expression: &Expression) -> bool {
+        matches!(self.reference.evaluate(expression), Some(Constant::Nil))
+    }
+
+    /// Compares the values of a statement with the values lined up from the source. Names
+    /// left without a value get `nil` as long as no call or `...` gives them its other values.
+    fn values(
+        &mut self,
+        expected: &[Value],
+        found: &Punctuated<Expression>,
+        mismatch: &dyn Fn() -> Mismatch,
+    ) -> Compared {
+        let found: Vec<&Expression> = found.iter().collect();
+        if found.len() > expected.len() {
+            return Err(mismatch());
+        }
+        for (value, &found) in expected.iter().zip(&found) {
+            match value {
+                Value::Expression(expression) => self.expression(expression, found)?,
+                Value::Nil if self.is_nil(found) => {}
+                Value::Nil => return Err(mismatch()),
+            }
+        }
+        let missing = &expected[found.len()..];
+        let all_nil = missing.iter().all(|value| match value {
+            Value::Expression(expression) => self.is_nil(expression),
+            Value::Nil => true,
+        });
+        if !missing.is_empty() && (!all_nil || found.last().is_some_and(|x| multiple_values(x))) {
+            return Err(mismatch());
+        }
+        Ok(())
+    }
+
+    fn list(
+        &mut self,
+        expected: &Punctuated<Expression>,
+        found: &Punctuated<Expression>,
+        expected_node: &(impl Node + Display),
+        found_node: &(impl Node + Display),
+    ) -> Compared {
+        if expected.len() != found.len() {
+            return Err(Mismatch::nodes(expected_node, found_node));
+        }
+        for (expected, found) in expected.iter().zip(found) {
+            self.expression(expected, found)?;
+        }
+        Ok(())
+    }
+
+    /// Compares two identifiers by what they refer to. Each local of the source must stand
+    /// for one local of the minified code throughout, and globals keep their names unless
+    /// they were mangled.
+    fn name(&mut self, expected: &TokenReference, found: &TokenReference) -> Compared {
+        let same = match (self.source.name(expected), self.output.name(found)) {
+            (Some(Name::Local(a)), Some(Name::Local(b))) => {
+                *self.pairs.entry(a).or_insert(b) == b && *self.reverse.entry(b).or_insert(a) == a
+            }
+            (Some(Name::Global(a)), Some(Name::Global(b))) => {
+                let name = self
+                    .renames
+                    .get(&position(expected))
+                    .unwrap_or(&self.source.scopes.globals[a].name);
+                self.output.scopes.globals[b].name == *name
+            }
+            (None, None) => expected.token().to_string() == found.token().to_string(),
+            _ => false,
+        };
+        match same {
+            true => Ok(()),
+            false => Err(Mismatch {
+                offset: position(expected),
+                expected: self.source.describe(expected),
+                found: self.output.describe(found),
+            }),
+        }
+    }
+
+    fn same_string(&self, expected: &TokenReference, found: &TokenReference) -> bool {
+        let target = self.reference.target;
+        match (
+            decode_string(expected.token(), target),
+            decode_string(found.token(), target),
+        ) {
+            (Some(expected), Some(found)) => expected == found,
+            // `--ascii-only` escapes the literals it can't decode as they are
+            _ => {
+                escape_non_ascii(&expected.token().to_string(), target)
+                    == escape_non_ascii(&found.token().to_string(), target)
+            }
+        }
+    }
+
+    /// Compares two tokens, identifiers by what they refer to and literals by their values
+    fn token(&mut self, expected: &TokenReference, found: &TokenReference) -> Compared {
+        let same = match (expected.token_type(), found.token_type()) {
+            (TokenType::Identifier { .. }, TokenType::Identifier { .. }) => {
+                return self.name(expected, found)
+            }
+            (TokenType::StringLiteral { .. }, TokenType::StringLiteral { .. }) => {
+                self.same_string(expected, found)
+            }
+            (TokenType::Number { text }, TokenType::Number { text: found_text }) => {
+                let target = self.reference.target;
+                match (parse_number(text, target), parse_number(found_text, target)) {
+                    (Some(expected), Some(found)) => expected == found,
+                    _ => text == found_text,
+                }
+            }
+            (
+                TokenType::InterpolatedString { literal, kind },
+                TokenType::InterpolatedString {
+                    literal: found_literal,
+                    kind: found_kind,
+                },
+            ) => {
+                let target = self.reference.target;
+                kind == found_kind
+                    && escape_non_ascii(literal, target) == escape_non_ascii(found_literal, target)
+            }
+            _ => expected.token().to_string() == found.token().to_string(),
+        };
+        match same {
+            true => Ok(()),
+            false => Err(Mismatch::nodes(expected, found)),
+        }
+    }
+
+    /// Compares nodes token by token, for the syntax the minifier writes back as it is
+    fn tokens(
+        &mut self,
+        expected: &(impl Node + Display),
+        found: &(impl Node + Display),
+    ) -> Compared {
+        let expected_tokens: Vec<&TokenReference> = expected.tokens().collect();
+        let found_tokens: Vec<&TokenReference> = found.tokens().collect();
+        if expected_tokens.len() != found_tokens.len() {
+            return Err(Mismatch::nodes(expected, found));
+        }
+        for (expected, found) in expected_tokens.into_iter().zip(found_tokens) {
+            self.token(expected, found)?;
+        }
+        Ok(())
+    }
+
+    fn optional<T: Node + Display>(
+        &mut self,
+        expected: Option<&T>,
+        found: Option<&T>,
+        fallback: usize,
+    ) -> Compared {
+        match (expected, found) {
+            (Some(expected), Some(found)) => self.tokens(expected, found),
+            (None, None) => Ok(()),
+            _ => {
+                let render = |node: Option<&T>| match node {
+                    Some(node) => render_node(node),
+                    None => String::from("nothing"),
+                };
+                Err(Mismatch {
+                    offset: expected.and_then(start).unwrap_or(fallback),
+                    expected: render(expected),
+                    found: render(found),
+                })
+            }
+        }
+    }
+
+    fn attributes<'n, T: Node + Display + 'n>(
+        &mut self,
+        expected: impl Iterator<Item = &'n T>,
+        found: impl Iterator<Item = &'n T>,
+        fallback: usize,
+    ) -> Compared {
+        let (expected, found): (Vec<&T>, Vec<&T>) = (expected.collect(), found.collect());
+        for i in 0..expected.len().max(found.len()) {
+            self.optional(expected.get(i).copied(), found.get(i).copied(), fallback)?;
+        }
+        Ok(())
+    }
+
+    fn body(&mut self, expected: &FunctionBody, found: &FunctionBody) -> Compared {
+        let offset = start(expected).unwrap_or_default();
+        self.optional(expected.generics(), found.generics(), offset)?;
+        if expected.parameters().len() != found.parameters().len() {
+            return Err(Mismatch::nodes(expected.parameters(), found.parameters()));
+        }
+        for (parameter, found_parameter) in expected.parameters().iter().zip(found.parameters()) {
+            match (parameter, found_parameter) {
+                (Parameter::Name(name), Parameter::Name(found_name)) => {
+                    self.name(name, found_name)?
+                }
+                (Parameter::Ellipsis(_), Parameter::Ellipsis(_)) => {}
+                _ => return Err(Mismatch::nodes(parameter, found_parameter)),
+            }
+        }
+        let type_specifiers: Vec<_> = expected.type_specifiers().collect();
+        let found_type_specifiers: Vec<_> = found.type_specifiers().collect();
+        for i in 0..type_specifiers.len().max(found_type_specifiers.len()) {
+            self.optional(
+                type_specifiers.get(i).copied().flatten(),
+                found_type_specifiers.get(i).copied().flatten(),
+                offset,
+            )?;
+        }
+        self.optional(expected.return_type(), found.return_type(), offset)?;
+        self.block(expected.block(), found.block(), offset)
+    }
+
+    /// Compares two expressions. An expression the reference evaluator knows the value of
+    /// may be written any way which has the same value, down to the subtype of numbers.
+    fn expression(&mut self, expected: &Expression, found: &Expression) -> Compared {
+        if let Some(value) = self.reference.evaluate(expected) {
+            return match self.reference.evaluate(found) {
+                Some(found_value) if value.same(&found_value) => Ok(()),
+                _ => Err(Mismatch::expressions(expected, found)),
+            };
+        }
+        match (expected, found) {
+            (
+                Expression::BinaryOperator { lhs, binop, rhs },
+                Expression::BinaryOperator {
+                    lhs: found_lhs,
+                    binop: found_binop,
+                    rhs: found_rhs,
+                },
+            ) if binop.token().token().to_string() == found_binop.token().token().to_string() => {
+                self.expression(lhs, found_lhs)?;
+                self.expression(rhs, found_rhs)
+            }
+            (
+                Expression::UnaryOperator { unop, expression },
+                Expression::UnaryOperator {
+                    unop: found_unop,
+                    expression: found_expression,
+                },
+            ) if unop.token().token().to_string() == found_unop.token().token().to_string() => {
+                self.expression(expression, found_expression)
+            }
+            (
+                Expression::Parentheses { expression, .. },
+                Expression::Parentheses {
+                    expression: found_expression,
+                    ..
+                },
+            ) => self.expression(expression, found_expression),
+            (Expression::Function(x), Expression::Function(y)) => {
+                let offset = start(expected).unwrap_or_default();
+                self.attributes(x.attributes(), y.attributes(), offset)?;
+                self.body(x.body(), y.body())
+            }
+            (Expression::FunctionCall(x), Expression::FunctionCall(y)) => self.function_call(x, y),
+            (Expression::IfExpression(x), Expression::IfExpression(y)) => {
+                let else_ifs: Vec<_> = x.else_if_expressions().into_iter().flatten().collect();
+                let found_else_ifs: Vec<_> =
+                    y.else_if_expressions().into_iter().flatten().collect();
+                if else_ifs.len() != found_else_ifs.len() {
+                    return Err(Mismatch::expressions(expected, found));
+                }
+                self.expression(x.condition(), y.condition())?;
+                self.expression(x.if_expression(), y.if_expression())?;
+                for (else_if, found_else_if) in else_ifs.into_iter().zip(found_else_ifs) {
+                    self.expression(else_if.condition(), found_else_if.condition())?;
+                    self.expression(else_if.expression(), found_else_if.expression())?;
+                }
+                self.expression(x.else_expression(), y.else_expression())
+            }
+            (Expression::InterpolatedString(x), Expression::InterpolatedString(y)) => {
+                let segments: Vec<_> = x.segments().collect();
+                let found_segments: Vec<_> = y.segments().collect();
+                if segments.len() != found_segments.len() {
+                    return Err(Mismatch::expressions(expected, found));
+                }
+                for (segment, found_segment) in segments.into_iter().zip(found_segments) {
+                    self.token(&segment.literal, &found_segment.literal)?;
+                    self.expression(&segment.expression, &found_segment.expression)?;
+                }
+                self.token(x.last_string(), y.last_string())
+            }
+            (Expression::TableConstructor(x), Expression::TableConstructor(y)) => self.table(x, y),
+            (
+                Expression::TypeAssertion {
+                    expression,
+                    type_assertion,
+                },
+                Expression::TypeAssertion {
+                    expression: found_expression,
+                    type_assertion: found_type_assertion,
+                },
+            ) => {
+                self.expression(expression, found_expression)?;
+                self.tokens(type_assertion, found_type_assertion)
+            }
+            (Expression::Var(x), Expression::Var(y)) => self.var(x, y),
+            (Expression::String(x), Expression::String(y)) if self.same_string(x, y) => Ok(()),
+            (Expression::Number(x), Expression::Number(y))
+            | (Expression::Symbol(x), Expression::Symbol(y)) => self.token(x, y),
+            _ => Err(Mismatch::expressions(expected, found)),
+        }
+    }
+
+    fn table(&mut self, expected: &TableConstructor, found: &TableConstructor) -> Compared {
+        if expected.fields().len() != found.fields().len() {
+            return Err(Mismatch::nodes(expected, found));
+        }
+        for (field, found_field) in expected.fields().iter().zip(found.fields()) {
+            match (field, found_field) {
+                (
+                    Field::NameKey { key, value, .. },
+                    Field::NameKey {
+                        key: found_key,
+                        value: found_value,
+                        ..
+                    },
+                ) if key.token().to_string() == found_key.token().to_string() => {
+                    self.expression(value, found_value)?
+                }
+                (
+                    Field::ExpressionKey { key, value, .. },
+                    Field::ExpressionKey {
+                        key: found_key,
+                        value: found_value,
+                        ..
+                    },
+                ) => {
+                    self.expression(key, found_key)?;
+                    self.expression(value, found_value)?;
+                }
+                (Field::NoKey(value), Field::NoKey(found_value)) => {
+                    self.expression(value, found_value)?
+                }
+                _ => return Err(Mismatch::nodes(field, found_field)),
+            }
+        }
+        Ok(())
+    }
+
+    fn var(&mut self, expected: &Var, found: &Var) -> Compared {
+        match (expected, found) {
+            (Var::Name(name), Var::Name(found_name)) => self.name(name, found_name),
+            (Var::Expression(x), Var::Expression(y)) => {
+                self.prefix(x.prefix(), y.prefix())?;
+                self.suffixes(
+                    &x.suffixes().collect::<Vec<_>>(),
+                    &y.suffixes().collect::<Vec<_>>(),
+                    expected,
+                    found,
+                )
+            }
+            _ => Err(Mismatch::nodes(expected, found)),
+        }
+    }
+
+    fn function_call(&mut self, expected: &FunctionCall, found: &FunctionCall) -> Compared {
+        self.prefix(expected.prefix(), found.prefix())?;
+        self.suffixes(
+            &expected.suffixes().collect::<Vec<_>>(),
+            &found.suffixes().collect::<Vec<_>>(),
+            expected,
+            found,
+        )
+    }
+
+    fn prefix(&mut self, expected: &Prefix, found: &Prefix) -> Compared {
+        match (expected, found) {
+            (Prefix::Name(name), Prefix::Name(found_name)) => self.name(name, found_name),
+            (Prefix::Expression(expression), Prefix::Expression(found_expression)) => {
+                self.expression(expression, found_expression)
+            }
+            _ => Err(Mismatch::nodes(expected, found)),
+        }
+    }
+
+    fn suffixes(
+        &mut self,
+        expected: &[&Suffix],
+        found: &[&Suffix],
+        expected_node: &(impl Node + Display),
+        found_node: &(impl Node + Display),
+    ) -> Compared {
+        if expected.len() != found.len() {
+            return Err(Mismatch::nodes(expected_node, found_node));
+        }
+        for (&suffix, &found_suffix) in expected.iter().zip(found) {
+            match (suffix, found_suffix) {
+                (Suffix::Call(Call::AnonymousCall(args)), Suffix::Call(Call::AnonymousCall(y))) => {
+                    self.function_args(args, y)?
+                }
+                (Suffix::Call(Call::MethodCall(x)), Suffix::Call(Call::MethodCall(y))) => {
+                    self.tokens(x.name(), y.name())?;
+                    self.optional(
+                        x.type_instantiation(),
+                        y.type_instantiation(),
+                        position(x.name()),
+                    )?;
+                    self.function_args(x.args(), y.args())?;
+                }
+                (
+                    Suffix::Index(Index::Brackets { expression, .. }),
+                    Suffix::Index(Index::Brackets {
+                        expression: found_expression,
+                        ..
+                    }),
+                ) => self.expression(expression, found_expression)?,
+                (
+                    Suffix::Index(Index::Dot { name, .. }),
+                    Suffix::Index(Index::Dot {
+                        name: found_name, ..
+                    }),
+                ) => self.tokens(name, found_name)?,
+                (suffix, found_suffix)
+                    if mem::discriminant(suffix) == mem::discriminant(found_suffix) =>
+                {
+                    self.tokens(suffix, found_suffix)?
+                }
+                _ => return Err(Mismatch::nodes(suffix, found_suffix)),
+            }
+        }
+        Ok(())
+    }
+
+    fn function_args(&mut self, expected: &FunctionArgs, found: &FunctionArgs) -> Compared {
+        match (expected, found) {
+            (
+                FunctionArgs::Parentheses { arguments, .. },
+                FunctionArgs::Parentheses {
+                    arguments: found_arguments,
+                    ..
+                },
+            ) => self.list(arguments, found_arguments, expected, found),
+            (FunctionArgs::String(x), FunctionArgs::String(y)) if self.same_string(x, y) => Ok(()),
+            (FunctionArgs::TableConstructor(x), FunctionArgs::TableConstructor(y)) => {
+                self.table(x, y)
+            }
+            _ => Err(Mismatch::nodes(expected, found)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Verifies `code` as the minified form of `source`, with no names mangled
+    fn check(source: &str, code: &str) -> Result<(), Error> {
+        let options = Options::default();
+        let ast = full_moon::parse_fallible(source, options.target.lua_version())
+            .into_result()
+            .unwrap();
+        verify(source, &ast, code, &HashMap::new(), &options)
+    }
+
+    fn rejected(source: &str, code: &str) -> bool {
+        matches!(check(source, code), Err(Error::Verify { .. }))
+    }
+
+    #[test]
+    fn accepts_equivalent_code() {
+        let source = "local alpha, beta = 1, 2\nprint(alpha + beta, 2 * 3)";
+        assert!(check(source, "local a,b=1,2 print(a+b,6)").is_ok());
+        assert!(check("local a = 1\nlocal b = 2", "local a,b=1,2").is_ok());
+        assert!(check("if false then x() end\nprint(1)", "print(1)").is_ok());
+    }
+
+    #[test]
+    fn rejects_swapped_renames() {
+        let source = "local alpha, beta = 1, 2\nprint(alpha, beta)";
+        assert!(rejected(source, "local a,b=1,2 print(b,a)"));
+        // Two locals of the source can't become one
+        assert!(rejected(source, "local a,a=1,2 print(a,a)"));
+        // A local can't become a global
+        assert!(rejected("local x = 1\nprint(x)", "local a=1 print(b)"));
+    }
+
+    #[test]
+    fn rejects_wrong_constants() {
+        assert!(rejected("print(1 + 2)", "print(4)"));
+        assert!(rejected("print('a' .. 'b')", "print('ba')"));
+        assert!(rejected("print(not nil)", "print(false)"));
+    }
+
+    #[test]
+    fn rejects_merges_which_change_values() {
+        let source = "local a, b = 1, 2\na = b\nb = a";
+        assert!(rejected(source, "local a,b=1,2 a,b=b,a"));
+        assert!(rejected("local a = f()\nlocal b", "local a,b=f()"));
+        assert!(rejected("x = 1\ny = f()", "x,y=1,f()"));
+    }
+
+    #[test]
+    fn rejects_removed_live_code() {
+        assert!(rejected("if true then x() end\nprint(1)", "print(1)"));
+        assert!(rejected("do return end\nprint(1)", "print(1)"));
+    }
+}
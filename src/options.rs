@@ -0,0 +1,124 @@
+use std::{collections::HashSet, fmt};
+
+use full_moon::LuaVersion;
+
+/// The banner of `Options::default`
+const BANNER: &str = concat!(
+    "Minified by luamine-rs v",
+    env!("CARGO_PKG_VERSION"),
+    "\nCode may not be usable, report bugs at: https://github.com/teppyboy/luamine-rs/issues"
+);
+
+/// Selects which passes `minify` runs
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Rename locals, parameters and upvalues to the shortest free names
+    pub rename_locals: bool,
+    /// Merge adjacent assignments when that can't change what the program does
+    pub merge_statements: bool,
+    /// Also rename globals assigned by the script, except for builtins and `keep_globals`.
+    /// Globals shared with other scripts must be listed in `keep_globals`.
+    pub mangle_globals: bool,
+    /// Globals which keep their names when mangling globals
+    pub keep_globals: HashSet<String>,
+    /// Re-parse the minified code and fail if it doesn't match the structure of the source
+    pub verify: bool,
+    /// What to do with Luau type syntax
+    pub types: TypeMode,
+    /// Evaluate expressions whose operands are all literals
+    pub fold_constants: bool,
+    /// Rewrite numeric literals in their shortest form
+    pub shorten_numbers: bool,
+    /// Rewrite string literals in their shortest form
+    pub shorten_strings: bool,
+    /// Escape every character outside of ASCII in string literals
+    pub ascii_only: bool,
+    /// Remove unreachable statements and branches whose condition is a constant
+    pub remove_dead_code: bool,
+    /// The Lua version the source is written in, which decides how it is parsed
+    pub dialect: Target,
+    /// The Lua version the minified code runs on, which decides what folded expressions evaluate
+    /// to and which syntax the code may use
+    pub target: Target,
+    /// Build a source map of the minified code
+    pub source_map: bool,
+    /// Text of the comment the minified code starts with, after the shebang, Luau directives
+    /// and `--[[! ... ]]` license comments of the source which are always kept
+    pub banner: Option<String>,
+}
+
+/// What to do with Luau type syntax
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TypeMode {
+    /// Minify annotations, type declarations and assertions like any other code
+    #[default]
+    Keep,
+    /// Remove all type syntax, leaving code which Lua 5.1 can run if it uses no other Luau syntax
+    Strip,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            rename_locals: true,
+            merge_statements: true,
+            mangle_globals: false,
+            keep_globals: HashSet::new(),
+            verify: false,
+            types: TypeMode::Keep,
+            fold_constants: true,
+            shorten_numbers: true,
+            shorten_strings: true,
+            ascii_only: false,
+            remove_dead_code: true,
+            dialect: Target::Luau,
+            target: Target::Luau,
+            source_map: false,
+            banner: Some(String::from(BANNER)),
+        }
+    }
+}
+
+/// A Lua version
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Target {
+    Lua51,
+    Lua52,
+    Lua53,
+    Lua54,
+    LuaJit,
+    #[default]
+    Luau,
+}
+
+impl Target {
+    /// Whether numbers have separate integer and float subtypes
+    pub fn has_integers(self) -> bool {
+        matches!(self, Target::Lua53 | Target::Lua54)
+    }
+
+    /// The grammar full_moon parses code of this version with
+    pub(crate) fn lua_version(self) -> LuaVersion {
+        match self {
+            Target::Lua51 => LuaVersion::lua51(),
+            Target::Lua52 => LuaVersion::lua52(),
+            Target::Lua53 => LuaVersion::lua53(),
+            Target::Lua54 => LuaVersion::lua54(),
+            Target::LuaJit => LuaVersion::luajit(),
+            Target::Luau => LuaVersion::luau(),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Target::Lua51 => "Lua 5.1",
+            Target::Lua52 => "Lua 5.2",
+            Target::Lua53 => "Lua 5.3",
+            Target::Lua54 => "Lua 5.4",
+            Target::LuaJit => "LuaJIT",
+            Target::Luau => "Luau",
+        })
+    }
+}
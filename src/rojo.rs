@@ -0,0 +1,218 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{Map, Value};
+
+use crate::Error;
+
+/// Extensions of the files Rojo turns into scripts
+const EXTENSIONS: &[&str] = &["luau", "lua"];
+
+/// An instance of the tree a Rojo project builds
+#[derive(Debug, Default)]
+struct Instance {
+    class: String,
+    /// The file of a script, or the init script of a directory
+    file: Option<PathBuf>,
+    children: BTreeMap<String, Instance>,
+}
+
+/// The instances a Rojo project file maps to files on disk, rooted at the `DataModel`
+#[derive(Debug)]
+pub struct Project {
+    root: Instance,
+    /// The instance path of every script
+    scripts: HashMap<PathBuf, Vec<String>>,
+}
+
+fn invalid(path: &Path, message: impl ToString) -> Error {
+    Error::InvalidProject {
+        path: path.to_path_buf(),
+        message: message.to_string(),
+    }
+}
+
+/// The name and class of the script a file is, like Rojo names them
+fn script(file_name: &str) -> Option<(&str, &'static str)> {
+    let stem = EXTENSIONS
+        .iter()
+        .find_map(|extension| file_name.strip_suffix(&format!(".{}", extension)))?;
+    Some(match stem {
+        _ if stem.ends_with(".server") => (&stem[..stem.len() - 7], "Script"),
+        _ if stem.ends_with(".client") => (&stem[..stem.len() - 7], "LocalScript"),
+        _ => (stem, "ModuleScript"),
+    })
+}
+
+/// The instance of a file or a directory, `None` for files which aren't scripts
+fn from_path(path: &Path) -> Result<Option<Instance>, Error> {
+    if path.is_file() {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        return Ok(script(&file_name).map(|(_, class)| Instance {
+            class: class.to_string(),
+            file: Some(path.to_path_buf()),
+            children: BTreeMap::new(),
+        }));
+    }
+    let mut instance = Instance {
+        class: String::from("Folder"),
+        ..Instance::default()
+    };
+    let mut entries: Vec<PathBuf> = fs::read_dir(path)
+        .map_err(|error| Error::io(path, error))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()
+        .map_err(|error| Error::io(path, error))?;
+    entries.sort();
+    for entry in entries {
+        let file_name = entry.file_name().unwrap_or_default().to_string_lossy();
+        if entry.is_file() {
+            match script(&file_name) {
+                // The directory itself is the script
+                Some(("init", class)) => {
+                    instance.class = class.to_string();
+                    instance.file = Some(entry.clone());
+                }
+                Some((name, _)) => {
+                    if let Some(child) = from_path(&entry)? {
+                        instance.children.insert(name.to_string(), child);
+                    }
+                }
+                None => {}
+            }
+        } else if let Some(child) = from_path(&entry)? {
+            instance.children.insert(file_name.to_string(), child);
+        }
+    }
+    Ok(Some(instance))
+}
+
+/// The instance of a node of a project tree, whose `$path` is relative to `directory`
+fn from_node(
+    node: &Map<String, Value>,
+    directory: &Path,
+    project: &Path,
+) -> Result<Instance, Error> {
+    let mut instance = match node.get("$path") {
+        Some(value) => {
+            // `$path` may also be written `{ "optional": path }`
+            let path = value
+                .as_str()
+                .or_else(|| value.get("optional").and_then(Value::as_str))
+                .ok_or_else(|| invalid(project, "`$path` must be a string"))?;
+            let path = directory.join(path);
+            if path.to_string_lossy().ends_with(".project.json") {
+                load_tree(&path)?.1
+            } else if path.exists() {
+                from_path(&path)?.unwrap_or_default()
+            } else {
+                Instance::default()
+            }
+        }
+        None => Instance::default(),
+    };
+    match node.get("$className").and_then(Value::as_str) {
+        Some(class) => instance.class = class.to_string(),
+        None if instance.class.is_empty() => instance.class = String::from("Folder"),
+        None => {}
+    }
+    for (name, child) in node.iter().filter(|(name, _)| !name.starts_with('$')) {
+        let child = child
+            .as_object()
+            .ok_or_else(|| invalid(project, format!("`{}` must be an object", name)))?;
+        instance
+            .children
+            .insert(name.clone(), from_node(child, directory, project)?);
+    }
+    Ok(instance)
+}
+
+/// The name of a project file and the instance at the root of its tree
+fn load_tree(path: &Path) -> Result<(String, Instance), Error> {
+    let text = fs::read_to_string(path).map_err(|error| Error::io(path, error))?;
+    let project: Value = serde_json::from_str(&text).map_err(|error| invalid(path, error))?;
+    let tree = project
+        .get("tree")
+        .and_then(Value::as_object)
+        .ok_or_else(|| invalid(path, "no `tree` object"))?;
+    let name = project
+        .get("name")
+        .and_then(Value::as_str)
+        .unwrap_or("Project")
+        .to_string();
+    let directory = path.parent().unwrap_or(Path::new(""));
+    Ok((name, from_node(tree, directory, path)?))
+}
+
+/// Records the instance path of every script under `instance`
+fn index(instance: &Instance, path: &mut Vec<String>, scripts: &mut HashMap<PathBuf, Vec<String>>) {
+    if let Some(file) = &instance.file {
+        if let Ok(file) = file.canonicalize() {
+            scripts.entry(file).or_insert_with(|| path.clone());
+        }
+    }
+    for (name, child) in &instance.children {
+        path.push(name.clone());
+        index(child, path, scripts);
+        path.pop();
+    }
+}
+
+impl Project {
+    /// Reads a `*.project.json` file and the files its tree points at
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let (name, tree) = load_tree(path)?;
+        let root = match tree.class.as_str() {
+            "DataModel" => tree,
+            // A library is only reachable from its own scripts, it's put under the DataModel so
+            // instance paths work the same
+            _ => Instance {
+                class: String::from("DataModel"),
+                file: None,
+                children: BTreeMap::from([(name, tree)]),
+            },
+        };
+        let mut scripts = HashMap::new();
+        index(&root, &mut Vec::new(), &mut scripts);
+        Ok(Project { root, scripts })
+    }
+
+    /// The instance path of the script a file is, from the `DataModel`
+    pub fn instance_path(&self, file: &Path) -> Option<&[String]> {
+        let file = file.canonicalize().ok()?;
+        self.scripts.get(&file).map(Vec::as_slice)
+    }
+
+    /// The file of the ModuleScript at an instance path, or why it can't be required
+    pub fn module(&self, path: &[String]) -> Result<&Path, String> {
+        let mut instance = &self.root;
+        for (i, name) in path.iter().enumerate() {
+            instance = instance.children.get(name).ok_or_else(|| {
+                format!(
+                    "{} has no child `{}` in the Rojo project",
+                    display(&path[..i]),
+                    name
+                )
+            })?;
+        }
+        match (&instance.file, instance.class.as_str()) {
+            (Some(file), "ModuleScript") => Ok(file),
+            (_, class) => Err(format!(
+                "{} is a {}, only ModuleScripts can be required",
+                display(path),
+                class
+            )),
+        }
+    }
+}
+
+/// Writes an instance path the way Luau code reaches it
+pub fn display(path: &[String]) -> String {
+    std::iter::once("game")
+        .chain(path.iter().map(String::as_str))
+        .collect::<Vec<&str>>()
+        .join(".")
+}
@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{minifier::emitter::Origin, Error};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A position of the minified code mapped back to the source. Lines and columns start at 0
+/// and columns count bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mapping {
+    pub generated_line: usize,
+    pub generated_column: usize,
+    /// Index of the source in `SourceMap::sources`
+    pub source: usize,
+    pub source_line: usize,
+    pub source_column: usize,
+    /// The name in the source of a renamed identifier
+    pub name: Option<String>,
+}
+
+/// A Source Map v3 of the minified code
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    /// Name of the minified file
+    pub file: Option<String>,
+    /// Paths of the sources. `minify` names its only source with an empty string, which
+    /// should be replaced by the path of the input.
+    pub sources: Vec<String>,
+    /// Sorted by generated position
+    pub mappings: Vec<Mapping>,
+}
+
+/// The JSON layout of a source map
+#[derive(Serialize, Deserialize)]
+struct Json {
+    version: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
+    sources: Vec<String>,
+    #[serde(default)]
+    names: Vec<String>,
+    mappings: String,
+}
+
+/// Byte offsets at which each line of `text` starts
+pub(crate) fn line_starts(text: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect()
+}
+
+/// Line and column of a byte offset
+pub(crate) fn line_column(line_starts: &[usize], offset: usize) -> (usize, usize) {
+    let line = line_starts.partition_point(|&start| start <= offset) - 1;
+    (line, offset - line_starts[line])
+}
+
+fn encode_vlq(output: &mut String, value: i64) {
+    let mut vlq = (value.unsigned_abs() << 1) | u64::from(value < 0);
+    loop {
+        let mut digit = (vlq & 31) as usize;
+        vlq >>= 5;
+        if vlq > 0 {
+            digit |= 32;
+        }
+        output.push(BASE64[digit] as char);
+        if vlq == 0 {
+            break;
+        }
+    }
+}
+
+/// Reads the values of a segment of the mappings
+fn decode_vlq(segment: &str) -> Option<Vec<i64>> {
+    let mut values = Vec::new();
+    let (mut value, mut shift) = (0u64, 0);
+    for c in segment.bytes() {
+        let digit = BASE64.iter().position(|&b| b == c)? as u64;
+        value |= (digit & 31).checked_shl(shift)?;
+        if digit & 32 == 0 {
+            let magnitude = (value >> 1) as i64;
+            values.push(if value & 1 == 1 {
+                -magnitude
+            } else {
+                magnitude
+            });
+            (value, shift) = (0, 0);
+        } else {
+            shift += 5;
+        }
+    }
+    (shift == 0).then_some(values)
+}
+
+impl SourceMap {
+    /// Maps the tokens of `code` back to `source` using the origins recorded by the emitter
+    pub(crate) fn new(source: &str, code: &str, origins: &[Origin]) -> Self {
+        let (source_lines, code_lines) = (line_starts(source), line_starts(code));
+        let mappings = origins
+            .iter()
+            .map(|origin| {
+                let (generated_line, generated_column) = line_column(&code_lines, origin.output);
+                let (source_line, source_column) = line_column(&source_lines, origin.source);
+                Mapping {
+                    generated_line,
+                    generated_column,
+                    source: 0,
+                    source_line,
+                    source_column,
+                    name: origin.name.clone(),
+                }
+            })
+            .collect();
+        SourceMap {
+            file: None,
+            sources: vec![String::new()],
+            mappings,
+        }
+    }
+
+    /// Serializes the map in the Source Map v3 format
+    pub fn to_json(&self) -> String {
+        let mut names: Vec<String> = Vec::new();
+        let mut name_indices: HashMap<&str, usize> = HashMap::new();
+        let mut mappings = String::new();
+        // Every field but the generated column is relative to the previous segment
+        let (mut line, mut column) = (0, 0);
+        let (mut source, mut source_line, mut source_column, mut name) = (0, 0, 0, 0);
+        for (i, mapping) in self.mappings.iter().enumerate() {
+            if mapping.generated_line > line {
+                for _ in line..mapping.generated_line {
+                    mappings.push(';');
+                }
+                line = mapping.generated_line;
+                column = 0;
+            } else if i > 0 {
+                mappings.push(',');
+            }
+            encode_vlq(&mut mappings, mapping.generated_column as i64 - column);
+            encode_vlq(&mut mappings, mapping.source as i64 - source);
+            encode_vlq(&mut mappings, mapping.source_line as i64 - source_line);
+            encode_vlq(&mut mappings, mapping.source_column as i64 - source_column);
+            column = mapping.generated_column as i64;
+            source = mapping.source as i64;
+            source_line = mapping.source_line as i64;
+            source_column = mapping.source_column as i64;
+            if let Some(original) = &mapping.name {
+                let index = *name_indices.entry(original).or_insert_with(|| {
+                    names.push(original.clone());
+                    names.len() - 1
+                });
+                encode_vlq(&mut mappings, index as i64 - name);
+                name = index as i64;
+            }
+        }
+        let json = Json {
+            version: 3,
+            file: self.file.clone(),
+            sources: self.sources.clone(),
+            names,
+            mappings,
+        };
+        serde_json::to_string(&json).unwrap()
+    }
+
+    /// Reads a map in the Source Map v3 format
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let json: Json =
+            serde_json::from_str(json).map_err(|error| Error::InvalidMap(error.to_string()))?;
+        if json.version != 3 {
+            return Err(Error::InvalidMap(format!(
+                "unsupported source map version {}",
+                json.version
+            )));
+        }
+        let invalid = || Error::InvalidMap(String::from("malformed mappings"));
+        let mut mappings = Vec::new();
+        let (mut source, mut source_line, mut source_column, mut name) = (0, 0, 0, 0);
+        for (line, segments) in json.mappings.split(';').enumerate() {
+            let mut column = 0;
+            for segment in segments.split(',').filter(|segment| !segment.is_empty()) {
+                let values = decode_vlq(segment).ok_or_else(invalid)?;
+                column += values[0];
+                // A segment with a single value maps to nothing
+                if values.len() < 4 {
+                    continue;
+                }
+                source += values[1];
+                source_line += values[2];
+                source_column += values[3];
+                let original = match values.get(4) {
+                    Some(delta) => {
+                        name += delta;
+                        Some(json.names.get(name as usize).ok_or_else(invalid)?.clone())
+                    }
+                    None => None,
+                };
+                let to_usize = |value: i64| usize::try_from(value).map_err(|_| invalid());
+                mappings.push(Mapping {
+                    generated_line: line,
+                    generated_column: to_usize(column)?,
+                    source: to_usize(source)?,
+                    source_line: to_usize(source_line)?,
+                    source_column: to_usize(source_column)?,
+                    name: original,
+                });
+            }
+        }
+        mappings.sort_by_key(|mapping| (mapping.generated_line, mapping.generated_column));
+        Ok(SourceMap {
+            file: json.file,
+            sources: json.sources,
+            mappings,
+        })
+    }
+
+    /// The mapping of the token at a generated position, which is the last mapping at or
+    /// before it on the same line
+    pub fn lookup(&self, line: usize, column: usize) -> Option<&Mapping> {
+        let end = self.mappings.partition_point(|mapping| {
+            (mapping.generated_line, mapping.generated_column) <= (line, column)
+        });
+        self.mappings[..end]
+            .last()
+            .filter(|mapping| mapping.generated_line == line)
+    }
+
+    /// Every mapping of a generated line
+    pub fn line(&self, line: usize) -> &[Mapping] {
+        let start = self
+            .mappings
+            .partition_point(|mapping| mapping.generated_line < line);
+        let end = self
+            .mappings
+            .partition_point(|mapping| mapping.generated_line <= line);
+        &self.mappings[start..end]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{minify, Options};
+
+    fn encoded(value: i64) -> String {
+        let mut output = String::new();
+        encode_vlq(&mut output, value);
+        output
+    }
+
+    #[test]
+    fn encodes_vlq() {
+        assert_eq!(encoded(0), "A");
+        assert_eq!(encoded(1), "C");
+        assert_eq!(encoded(-1), "D");
+        assert_eq!(encoded(15), "e");
+        assert_eq!(encoded(16), "gB");
+        assert_eq!(encoded(-16), "hB");
+        assert_eq!(encoded(1000), "w+B");
+    }
+
+    #[test]
+    fn decodes_what_it_encodes() {
+        let values = [
+            0,
+            1,
+            -1,
+            15,
+            -15,
+            16,
+            -16,
+            511,
+            512,
+            -1000,
+            123456789,
+            -(1 << 40),
+        ];
+        let mut segment = String::new();
+        for value in values {
+            encode_vlq(&mut segment, value);
+        }
+        assert_eq!(decode_vlq(&segment), Some(values.to_vec()));
+        assert_eq!(decode_vlq("AAgBC"), Some(vec![0, 0, 16, 1]));
+        // A continuation digit with nothing after it, and a character outside of base64
+        assert_eq!(decode_vlq("g"), None);
+        assert_eq!(decode_vlq("A!"), None);
+    }
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let mapping =
+            |generated_line, generated_column, source_line, source_column, name| Mapping {
+                generated_line,
+                generated_column,
+                source: 0,
+                source_line,
+                source_column,
+                name,
+            };
+        let map = SourceMap {
+            file: Some(String::from("out.lua")),
+            sources: vec![String::from("in.lua")],
+            mappings: vec![
+                mapping(0, 0, 3, 4, None),
+                mapping(0, 6, 10, 2, Some(String::from("alpha"))),
+                mapping(0, 40, 1, 0, Some(String::from("beta"))),
+                mapping(2, 3, 0, 30, Some(String::from("alpha"))),
+            ],
+        };
+        let read = SourceMap::from_json(&map.to_json()).unwrap();
+        assert_eq!(read.file, map.file);
+        assert_eq!(read.sources, map.sources);
+        assert_eq!(read.mappings, map.mappings);
+    }
+
+    #[test]
+    fn looks_up_minified_positions() {
+        let options = Options {
+            source_map: true,
+            banner: None,
+            ..Options::default()
+        };
+        let output = minify("local alpha = 1\nprint(alpha)\n", &options).unwrap();
+        assert_eq!(output.code, "local a=1 print(a)");
+        let map = output.source_map.unwrap();
+        let renamed = map.lookup(0, 16).unwrap();
+        assert_eq!((renamed.source_line, renamed.source_column), (1, 6));
+        assert_eq!(renamed.name.as_deref(), Some("alpha"));
+        // A position between tokens belongs to the token before it
+        let value = map.lookup(0, 9).unwrap();
+        assert_eq!((value.source_line, value.source_column), (0, 14));
+        assert_eq!(value.name, None);
+        assert_eq!(map.lookup(1, 0), None);
+    }
+}
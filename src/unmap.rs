@@ -0,0 +1,411 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    source_map::{line_column, line_starts},
+    Error, Rename, RenameKind, SourceMap,
+};
+
+/// Words after which Lua error messages and tracebacks quote the name of a variable
+const NAME_KINDS: &[&str] = &["global", "local", "upvalue", "function"];
+
+/// Characters which can't be part of the file name in front of a `:line:column` location
+const LOCATION_DELIMITERS: &[u8] = b" \t\r\n'\"`()<>[]{},;=";
+
+/// How Lua names a chunk loaded from a string, up to the code it quotes
+const STRING_CHUNK: &str = "[string \"";
+
+/// The names the minifier changed, with the lines of the source where they appear
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RenameMap {
+    pub renames: Vec<RenamedName>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RenamedName {
+    pub kind: RenameKind,
+    /// The name in the source
+    pub original: String,
+    /// The name in the minified code
+    pub renamed: String,
+    /// 1-based lines of the source with the declaration or a reference
+    pub lines: Vec<usize>,
+    /// Byte offsets in the minified code of the declaration and the references which are left
+    #[serde(default)]
+    pub offsets: Vec<usize>,
+}
+
+impl RenameMap {
+    pub fn new(source: &str, renames: &[Rename]) -> Self {
+        let starts = line_starts(source);
+        let renames = renames
+            .iter()
+            .map(|rename| {
+                let lines: BTreeSet<usize> = rename
+                    .positions
+                    .iter()
+                    .map(|&offset| line_column(&starts, offset).0 + 1)
+                    .collect();
+                RenamedName {
+                    kind: rename.kind,
+                    original: rename.original.clone(),
+                    renamed: rename.renamed.clone(),
+                    lines: lines.into_iter().collect(),
+                    offsets: rename.output_positions.clone(),
+                }
+            })
+            .collect();
+        RenameMap { renames }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        serde_json::from_str(json).map_err(|error| Error::InvalidMap(error.to_string()))
+    }
+
+    /// The original of a name, if only one name of `kinds` was renamed to it. Otherwise the
+    /// names which appear on one of the source `lines` are the only candidates.
+    fn original(&self, renamed: &str, kinds: &[RenameKind], lines: &[usize]) -> Option<&str> {
+        let candidates: Vec<&RenamedName> = self
+            .renames
+            .iter()
+            .filter(|name| name.renamed == renamed && kinds.contains(&name.kind))
+            .collect();
+        unique_original(&candidates).or_else(|| {
+            let on_lines: Vec<&RenamedName> = candidates
+                .into_iter()
+                .filter(|name| name.lines.iter().any(|line| lines.contains(line)))
+                .collect();
+            unique_original(&on_lines)
+        })
+    }
+}
+
+fn unique_original<'a>(names: &[&'a RenamedName]) -> Option<&'a str> {
+    let originals: BTreeSet<&str> = names.iter().map(|name| name.original.as_str()).collect();
+    (originals.len() == 1).then(|| *originals.first().unwrap())
+}
+
+/// A `file:line` or `file:line:column` location in a stack trace
+struct Location {
+    start: usize,
+    /// Where the file name ends
+    path_end: usize,
+    end: usize,
+    line: usize,
+    column: Option<usize>,
+}
+
+fn digits(text: &[u8], start: usize) -> Option<(usize, usize)> {
+    let end = start
+        + text[start..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+    let value = std::str::from_utf8(&text[start..end]).ok()?.parse().ok()?;
+    Some((value, end))
+}
+
+fn locations(text: &str) -> Vec<Location> {
+    let bytes = text.as_bytes();
+    let mut locations = Vec::new();
+    let mut i = 0;
+    while i + 1 < bytes.len() {
+        if bytes[i] != b':' || !bytes[i + 1].is_ascii_digit() {
+            i += 1;
+            continue;
+        }
+        // The name of a chunk loaded from a string quotes its code, delimiters and all
+        let start = match text[..i].rfind(STRING_CHUNK) {
+            Some(index) if text[..i].ends_with("\"]") => index,
+            _ => bytes[..i]
+                .iter()
+                .rposition(|c| LOCATION_DELIMITERS.contains(c))
+                .map_or(0, |index| index + 1),
+        };
+        let Some((line, mut end)) = digits(bytes, i + 1) else {
+            i += 1;
+            continue;
+        };
+        let mut column = None;
+        if bytes.get(end) == Some(&b':') && bytes.get(end + 1).is_some_and(u8::is_ascii_digit) {
+            if let Some((value, column_end)) = digits(bytes, end + 1) {
+                column = Some(value);
+                end = column_end;
+            }
+        }
+        if start < i && line > 0 {
+            locations.push(Location {
+                start,
+                path_end: i,
+                end,
+                line,
+                column,
+            });
+        }
+        i = end;
+    }
+    locations
+}
+
+/// Where a location of the minified code comes from, as a 1-based source line and column.
+/// A column points at the start of the token it is in. A location without a column is only
+/// mapped if its whole line comes from one source line. Stock Lua prints no columns and the
+/// minifier writes a single line, so its locations almost never resolve and are left as they
+/// are rather than pointing at a guess.
+fn resolve(source_map: &SourceMap, location: &Location) -> Option<(usize, usize, Option<usize>)> {
+    let line = location.line - 1;
+    match location.column {
+        Some(column) => source_map
+            .lookup(line, column.saturating_sub(1))
+            .map(|mapping| {
+                (
+                    mapping.source,
+                    mapping.source_line + 1,
+                    Some(mapping.source_column + 1),
+                )
+            }),
+        None => {
+            let mappings = source_map.line(line);
+            let first = mappings.first()?;
+            mappings
+                .iter()
+                .all(|mapping| {
+                    (mapping.source, mapping.source_line) == (first.source, first.source_line)
+                })
+                .then_some((first.source, first.source_line + 1, None))
+        }
+    }
+}
+
+/// Rewrites the locations of `line` with `source_map`, and returns the source lines they
+/// point at
+fn unmap_locations(line: &str, source_map: &SourceMap, output: &mut String) -> Vec<usize> {
+    let mut lines = Vec::new();
+    let mut last = 0;
+    for location in locations(line) {
+        let Some((source, source_line, column)) = resolve(source_map, &location) else {
+            continue;
+        };
+        output.push_str(&line[last..location.start]);
+        match source_map
+            .sources
+            .get(source)
+            .filter(|path| !path.is_empty())
+        {
+            Some(path) => output.push_str(path),
+            // Keep the file name of the trace when the map doesn't name its source
+            None => output.push_str(&line[location.start..location.path_end]),
+        }
+        output.push_str(&format!(":{}", source_line));
+        if let Some(column) = column {
+            output.push_str(&format!(":{}", column));
+        }
+        lines.push(source_line);
+        last = location.end;
+    }
+    output.push_str(&line[last..]);
+    lines
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Restores the renamed names which error messages and tracebacks quote in `line`
+fn unmap_names(line: &str, rename_map: &RenameMap, lines: &[usize]) -> String {
+    let mut output = String::new();
+    let mut rest = line;
+    while let Some((index, kind)) = NAME_KINDS
+        .iter()
+        .filter_map(|kind| Some((rest.find(&format!("{} ", kind))?, *kind)))
+        .min()
+    {
+        let name_start = index + kind.len() + 1;
+        // Tracebacks quote names, except for Roblox which writes `function name`
+        let quoted = rest[name_start..].starts_with('\'');
+        let name_start = name_start + usize::from(quoted);
+        let name_end = rest[name_start..]
+            .find(|c| !is_name_char(c))
+            .map_or(rest.len(), |end| name_start + end);
+        output.push_str(&rest[..name_start]);
+        let name = &rest[name_start..name_end];
+        let kinds: &[RenameKind] = match kind {
+            "global" => &[RenameKind::Global],
+            "function" => &[RenameKind::Local, RenameKind::Global],
+            _ => &[RenameKind::Local],
+        };
+        let valid = (quoted || kind == "function")
+            && !rest[..index].ends_with(is_name_char)
+            && name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_');
+        match rename_map.original(name, kinds, lines).filter(|_| valid) {
+            Some(original) => output.push_str(original),
+            None => output.push_str(name),
+        }
+        rest = &rest[name_end..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Rewrites a stack trace of minified code in terms of the source: `file:line:column`
+/// locations are mapped back with `source_map`, and renamed variables get their names
+/// back from `rename_map`
+pub fn unmap(
+    trace: &str,
+    source_map: Option<&SourceMap>,
+    rename_map: Option<&RenameMap>,
+) -> String {
+    let mut output = String::new();
+    for line in trace.split_inclusive('\n') {
+        let mut unmapped = String::new();
+        let lines = match source_map {
+            Some(source_map) => unmap_locations(line, source_map, &mut unmapped),
+            None => {
+                unmapped.push_str(line);
+                Vec::new()
+            }
+        };
+        match rename_map {
+            Some(rename_map) => output.push_str(&unmap_names(&unmapped, rename_map, &lines)),
+            None => output.push_str(&unmapped),
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Mapping;
+
+    /// The file, line and column of each location found in `text`
+    fn found(text: &str) -> Vec<(&str, usize, Option<usize>)> {
+        locations(text)
+            .iter()
+            .map(|location| {
+                (
+                    &text[location.start..location.path_end],
+                    location.line,
+                    location.column,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_locations() {
+        assert_eq!(
+            found("lua: script.lua:12: attempt to call a nil value"),
+            [("script.lua", 12, None)]
+        );
+        assert_eq!(
+            found("script.lua:3:15: boom"),
+            [("script.lua", 3, Some(15))]
+        );
+        assert_eq!(
+            found(r"C:\game\main.lua:4: in function 'f'"),
+            [(r"C:\game\main.lua", 4, None)]
+        );
+        assert_eq!(
+            found(r#"[string "local a=1 print(a.b)..."]:1: attempt to index"#),
+            [(r#"[string "local a=1 print(a.b)..."]"#, 1, None)]
+        );
+        assert_eq!(
+            found("\t(tail call): ?\n\tmain.lua:1: in main chunk"),
+            [("main.lua", 1, None)]
+        );
+        // No file name, or no line
+        assert_eq!(found("error at :5"), []);
+        assert_eq!(found("script.lua:0: x"), []);
+    }
+
+    fn rename(kind: RenameKind, original: &str, renamed: &str, lines: &[usize]) -> RenamedName {
+        RenamedName {
+            kind,
+            original: original.to_string(),
+            renamed: renamed.to_string(),
+            lines: lines.to_vec(),
+            offsets: Vec::new(),
+        }
+    }
+
+    fn rename_map() -> RenameMap {
+        RenameMap {
+            renames: vec![
+                rename(RenameKind::Local, "alpha", "a", &[1, 2]),
+                rename(RenameKind::Local, "beta", "a", &[5]),
+                rename(RenameKind::Local, "delta", "c", &[7]),
+                rename(RenameKind::Global, "gamma", "b", &[3]),
+            ],
+        }
+    }
+
+    #[test]
+    fn restores_names() {
+        let map = rename_map();
+        assert_eq!(
+            unmap_names("attempt to call global 'b' (a nil value)", &map, &[]),
+            "attempt to call global 'gamma' (a nil value)"
+        );
+        assert_eq!(
+            unmap_names("in upvalue 'c'", &map, &[]),
+            "in upvalue 'delta'"
+        );
+        // Roblox doesn't quote the name of a function
+        assert_eq!(
+            unmap_names("Script 'x', Line 1 - function c", &map, &[]),
+            "Script 'x', Line 1 - function delta"
+        );
+        // Only quoted names follow `local`, and `global` has to be a whole word
+        assert_eq!(unmap_names("local c", &map, &[]), "local c");
+        assert_eq!(unmap_names("noglobal 'b'", &map, &[]), "noglobal 'b'");
+    }
+
+    #[test]
+    fn leaves_ambiguous_names_to_their_lines() {
+        let map = rename_map();
+        let message = "attempt to index local 'a' (a nil value)";
+        assert_eq!(unmap_names(message, &map, &[]), message);
+        assert_eq!(
+            unmap_names(message, &map, &[5]),
+            "attempt to index local 'beta' (a nil value)"
+        );
+        // Both candidates appear on the lines
+        assert_eq!(unmap_names(message, &map, &[2, 5]), message);
+    }
+
+    #[test]
+    fn unmaps_traces() {
+        let mapping = |generated_column, source_line, source_column| Mapping {
+            generated_line: 0,
+            generated_column,
+            source: 0,
+            source_line,
+            source_column,
+            name: None,
+        };
+        let source_map = SourceMap {
+            file: None,
+            sources: vec![String::from("src/main.lua")],
+            mappings: vec![mapping(0, 0, 0), mapping(10, 4, 2), mapping(20, 4, 9)],
+        };
+        assert_eq!(
+            unmap(
+                "main.min.lua:1:15: attempt to call local 'a'",
+                Some(&source_map),
+                Some(&rename_map())
+            ),
+            "src/main.lua:5:3: attempt to call local 'beta'"
+        );
+        // The line comes from two lines of the source
+        assert_eq!(
+            unmap("main.min.lua:1: oops", Some(&source_map), None),
+            "main.min.lua:1: oops"
+        );
+    }
+}